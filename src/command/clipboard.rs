@@ -0,0 +1,72 @@
+use crate::view::View;
+use ::clipboard::{ClipboardContext, ClipboardProvider};
+use std::collections::VecDeque;
+
+const HISTORY_SIZE: usize = 32;
+
+/// Copies to the system clipboard when there is one, and to the terminal through the OSC 52
+/// escape sequence otherwise. The last texts copied or pasted are always kept in an internal
+/// history, so that pasting works even when neither of them can be read and earlier entries can
+/// be brought back.
+pub struct Clipboard {
+    system: Option<ClipboardContext>,
+    history: VecDeque<String>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Clipboard {
+            system: ClipboardProvider::new().ok(),
+            history: VecDeque::new(),
+        }
+    }
+
+    // Clipboard keeping to its history, leaving that of the system alone
+    #[cfg(test)]
+    pub fn internal() -> Self {
+        Clipboard {
+            system: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn copy(&mut self, view: &View, text: String) {
+        let copied = match self.system {
+            Some(ref mut ctx) => ctx.set_contents(text.clone()).is_ok(),
+            None => false,
+        };
+        if !copied {
+            // the terminal may not support it, in which case the register is all we have
+            view.copy_to_terminal(text.clone());
+        }
+        self.remember(text);
+    }
+
+    pub fn paste(&mut self) -> String {
+        let last = self.history.front().cloned().unwrap_or_default();
+        let text = match self.system {
+            Some(ref mut ctx) => ctx.get_contents().unwrap_or(last),
+            None => last,
+        };
+        self.remember(text.clone());
+        text
+    }
+
+    // Entry of the history, the most recent one being at index 0
+    pub fn history(&self, index: usize) -> Option<&String> {
+        self.history.get(index)
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    fn remember(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.history.retain(|entry| *entry != text);
+        self.history.push_front(text);
+        self.history.truncate(HISTORY_SIZE);
+    }
+}
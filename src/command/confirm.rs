@@ -0,0 +1,91 @@
+use super::line_edit::LineEdit;
+use super::save;
+use super::{PromptAction, State};
+use crate::data::*;
+use crate::view::View;
+use termion::event::{Event, Key};
+
+impl State {
+    pub(super) fn handle_confirm<T>(
+        content: &mut T,
+        view: &mut View,
+        event: Event,
+        prompt: String,
+        action: PromptAction,
+    ) -> Self
+    where
+        T: Saveable,
+    {
+        // what comes once the buffer is dealt with
+        let next = match action {
+            PromptAction::ConfirmQuit(_, ref discarded) => State::QuitAll(discarded.clone()),
+            _ => State::Close,
+        };
+        match event {
+            Event::Key(Key::Char('s')) | Event::Key(Key::Char('y')) => {
+                view.hide_list();
+                if content.name().is_empty() {
+                    return save::start_save_as(
+                        content,
+                        view,
+                        PromptAction::SaveThen(Box::new(next)),
+                    );
+                }
+                match content.save() {
+                    Ok(_) => next,
+                    Err(e) => {
+                        view.message(&e.to_string());
+                        State::Message
+                    }
+                }
+            }
+            Event::Key(Key::Char('d')) | Event::Key(Key::Char('n')) => {
+                view.hide_list();
+                match action {
+                    PromptAction::ConfirmQuit(buffer, mut discarded) => {
+                        discarded.push(buffer);
+                        State::QuitAll(discarded)
+                    }
+                    _ => State::Close,
+                }
+            }
+            Event::Key(Key::Char('c')) | Event::Key(Key::Esc) | Event::Key(Key::Ctrl('q')) => {
+                view.hide_list();
+                view.quiet();
+                State::Insert
+            }
+            _ => State::Prompt(prompt, LineEdit::default(), action),
+        }
+    }
+
+    pub(super) fn confirm_close<T: Named>(content: &T, view: &mut View) -> Self {
+        let prompt = format!(
+            "Save changes to {}? (s)ave (d)iscard (c)ancel: ",
+            display_name(content)
+        );
+        view.prompt(&prompt, "");
+        State::Prompt(prompt, LineEdit::default(), PromptAction::ConfirmClose)
+    }
+
+    // Asks what to do with the changes to the first of the modified buffers before quitting,
+    // listing all of them
+    pub fn confirm_quit(
+        view: &mut View,
+        names: Vec<String>,
+        buffer: usize,
+        discarded: Vec<usize>,
+    ) -> Self {
+        let prompt = format!(
+            "{} buffer(s) with changes not saved. Save changes to {}? (s)ave (d)iscard (c)ancel: ",
+            names.len(),
+            names[0]
+        );
+        view.prompt(&prompt, "");
+        view.show_list(names, 0);
+        State::Prompt(
+            prompt,
+            LineEdit::default(),
+            PromptAction::ConfirmQuit(buffer, discarded),
+        )
+    }
+}
@@ -0,0 +1,31 @@
+use super::clipboard::Clipboard;
+use super::history::History;
+use super::keymap::Keymap;
+use super::vi::Vi;
+use crate::data::Search;
+use std::collections::HashMap;
+
+/// What is kept from one state to the next, and from one file to the next
+pub struct Context {
+    pub clipboard: Clipboard,
+    pub search_history: History,
+    pub last_search: Option<Search>,
+    // entries of each kind of prompt
+    pub prompt_history: HashMap<&'static str, History>,
+    pub keymap: Keymap,
+    // modal editing, when turned on
+    pub vi: Option<Vi>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Context {
+            clipboard: Clipboard::new(),
+            search_history: History::new(),
+            last_search: None,
+            prompt_history: HashMap::new(),
+            keymap: Keymap::new(),
+            vi: None,
+        }
+    }
+}
@@ -0,0 +1,797 @@
+use super::line_edit::{self, LineEdit};
+use super::{goto, save, PromptAction, State};
+use crate::config::Overrides;
+use crate::data::*;
+use crate::view::View;
+use regex::{Regex, RegexBuilder};
+use std::io::Write;
+use std::process::{Command as Process, Stdio};
+use std::thread;
+
+const PROMPT: &str = ":";
+
+// Names the commands can be completed to
+const COMMANDS: &[&str] = &[
+    "edit",
+    "goto",
+    "qall",
+    "quit",
+    "set",
+    "substitute",
+    "wq",
+    "write",
+];
+
+// Commands taking a file name
+const FILE_COMMANDS: &[&str] = &["e", "edit", "w", "write", "wq", "x"];
+
+// Options of the set command, along with whether they take a value
+const OPTIONS: &[(&str, bool)] = &[
+    ("tabwidth", true),
+    ("scrollspeed", true),
+    ("softtabs", false),
+    ("numbers", false),
+    ("autoindent", false),
+];
+
+// Opens the command line, starting with the given text such as a range
+pub(super) fn start_command_line(view: &mut View, text: &str) -> State {
+    let mut line = LineEdit::default();
+    line.set_text(text);
+    line.show(view, PROMPT);
+    State::Prompt(PROMPT.to_string(), line, PromptAction::Ex)
+}
+
+// Runs a command such as "w file", "%s/a/b/g", "set tabwidth=2" or "1,3!sort"
+pub(super) fn run<T>(content: &mut T, view: &mut View, input: &str) -> State
+where
+    T: Editable + Saveable + Modifiable + Selectable + Undoable,
+{
+    let input = input.trim();
+    if input.is_empty() {
+        view.quiet();
+        return State::Insert;
+    }
+    // positions as the go to prompt takes them, such as "120", "12:4" or "+10"
+    if input
+        .chars()
+        .all(|c| c.is_ascii_digit() || ":+-".contains(c))
+    {
+        return goto::goto(content, view, input);
+    }
+    match parse(content, input) {
+        Ok((range, command)) => match execute(content, view, range, command) {
+            Ok(state) => state,
+            Err(e) => {
+                view.message(&e);
+                State::Message
+            }
+        },
+        Err(e) => {
+            view.message(&e);
+            State::Message
+        }
+    }
+}
+
+// Command lines the input can be completed to
+pub(super) fn candidates(input: &str) -> Vec<String> {
+    let (name, arg) = match input.find(' ') {
+        Some(i) => (&input[..i], input[i..].trim_start()),
+        None => {
+            return COMMANDS
+                .iter()
+                .filter(|c| c.starts_with(input))
+                .map(|c| c.to_string())
+                .collect();
+        }
+    };
+    if FILE_COMMANDS.contains(&name) {
+        return line_edit::path_candidates(arg)
+            .into_iter()
+            .map(|path| format!("{} {}", name, path))
+            .collect();
+    }
+    if name != "set" {
+        return Vec::new();
+    }
+    // only the last option gets completed
+    let (before, last) = match input.rfind(' ') {
+        Some(i) => input.split_at(i + 1),
+        None => ("", input),
+    };
+    let (negated, option) = match last.strip_prefix("no") {
+        Some(option) => ("no", option),
+        None => ("", last),
+    };
+    OPTIONS
+        .iter()
+        .filter(|&&(name, valued)| (negated.is_empty() || !valued) && name.starts_with(option))
+        .map(|&(name, valued)| {
+            format!(
+                "{}{}{}{}",
+                before,
+                negated,
+                name,
+                if valued { "=" } else { "" }
+            )
+        })
+        .collect()
+}
+
+// Lines a command applies to, both included
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    first: usize,
+    last: usize,
+}
+
+#[derive(Debug)]
+enum ExCommand {
+    Write(Option<String>),
+    // close, even with unsaved changes when forced
+    Quit(bool),
+    QuitAll,
+    // save and close, writing only unsaved changes for x
+    WriteQuit(Option<String>, bool),
+    Edit(String),
+    Set(String),
+    Goto(String),
+    Substitute(String),
+    Shell(String),
+    // nothing but a range, going to its last line
+    Jump,
+}
+
+fn parse<T: Editable>(content: &T, input: &str) -> Result<(Option<Range>, ExCommand), String> {
+    let (range, rest) = parse_range(content, input)?;
+    let rest = rest.trim_start();
+    if let Some(cmd) = rest.strip_prefix('!') {
+        return Ok((range, ExCommand::Shell(cmd.trim().to_string())));
+    }
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let (name, rest) = rest.split_at(name_len);
+    if name == "s" || name == "substitute" {
+        return Ok((range, ExCommand::Substitute(rest.to_string())));
+    }
+    let (forced, arg) = match rest.strip_prefix('!') {
+        Some(arg) => (true, arg.trim()),
+        None => (false, rest.trim()),
+    };
+    if !rest.is_empty() && !forced && !rest.starts_with(' ') {
+        return Err(format!("Not a command: {}", input));
+    }
+    let file = if arg.is_empty() {
+        None
+    } else {
+        Some(arg.to_string())
+    };
+    let command = match name {
+        "" if arg.is_empty() && range.is_some() => ExCommand::Jump,
+        "w" | "write" => ExCommand::Write(file),
+        "q" | "quit" if file.is_none() => ExCommand::Quit(forced),
+        "qa" | "qall" if file.is_none() => ExCommand::QuitAll,
+        "wq" => ExCommand::WriteQuit(file, false),
+        "x" => ExCommand::WriteQuit(file, true),
+        "e" | "edit" => match file {
+            Some(file) => ExCommand::Edit(file),
+            None => return Err("No file name given".to_string()),
+        },
+        "set" => ExCommand::Set(arg.to_string()),
+        "goto" => ExCommand::Goto(arg.to_string()),
+        _ => return Err(format!("Not a command: {}", input)),
+    };
+    if range.is_some() && !matches!(command, ExCommand::Jump) {
+        return Err(format!("No range allowed for {}", name));
+    }
+    Ok((range, command))
+}
+
+// Parses the range at the start of the input, "%" for every line, or one or two addresses
+// separated by a comma
+fn parse_range<'a, T: Editable>(
+    content: &T,
+    input: &'a str,
+) -> Result<(Option<Range>, &'a str), String> {
+    let last_line = content.line_count().saturating_sub(1);
+    if let Some(rest) = input.strip_prefix('%') {
+        let range = Range {
+            first: 0,
+            last: last_line,
+        };
+        return Ok((Some(range), rest));
+    }
+    let (first, rest) = match parse_address(content, input)? {
+        Some(address) => address,
+        None => return Ok((None, input)),
+    };
+    let (last, rest) = match rest.strip_prefix(',') {
+        Some(rest) => match parse_address(content, rest)? {
+            Some(address) => address,
+            None => return Err(format!("Missing end of range in {}", input)),
+        },
+        None => (first, rest),
+    };
+    if first > last_line || last > last_line {
+        return Err(format!("Past the last line, {}", last_line + 1));
+    }
+    let range = Range {
+        first: first.min(last),
+        last: first.max(last),
+    };
+    Ok((Some(range), rest))
+}
+
+// Parses a line as ".", "$" or a number, followed by lines to add or remove such as "+2"
+fn parse_address<'a, T: Editable>(
+    content: &T,
+    input: &'a str,
+) -> Result<Option<(usize, &'a str)>, String> {
+    let (mut line, mut rest) = if let Some(rest) = input.strip_prefix('.') {
+        (content.line() as isize, rest)
+    } else if let Some(rest) = input.strip_prefix('$') {
+        (content.line_count() as isize - 1, rest)
+    } else if input.starts_with(|c: char| c.is_ascii_digit()) {
+        let (number, rest) = split_number(input);
+        (number as isize - 1, rest)
+    } else if input.starts_with('+') || input.starts_with('-') {
+        (content.line() as isize, input)
+    } else {
+        return Ok(None);
+    };
+    while rest.starts_with('+') || rest.starts_with('-') {
+        let sign = if rest.starts_with('+') { 1 } else { -1 };
+        let (number, after) = split_number(&rest[1..]);
+        // a sign on its own counts as one line
+        let number = if after.len() == rest.len() - 1 {
+            1
+        } else {
+            number
+        };
+        line += sign * number as isize;
+        rest = after;
+    }
+    if line < 0 {
+        return Err("Before the first line".to_string());
+    }
+    Ok(Some((line as usize, rest)))
+}
+
+fn split_number(input: &str) -> (usize, &str) {
+    let len = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    (input[..len].parse().unwrap_or(0), &input[len..])
+}
+
+// Whether writing to the file leaves the content under its own name
+fn is_copy<T: Named>(content: &T, file: &str) -> bool {
+    !content.name().is_empty() && !same_file(content.name(), file)
+}
+
+fn execute<T>(
+    content: &mut T,
+    view: &mut View,
+    range: Option<Range>,
+    command: ExCommand,
+) -> Result<State, String>
+where
+    T: Editable + Saveable + Modifiable + Selectable + Undoable,
+{
+    let state = match command {
+        // another file gets a copy, unless the content has no name yet
+        ExCommand::Write(Some(file)) if is_copy(content, &file) => {
+            save::write_copy(content, &file)?;
+            view.message(&format!("Wrote a copy to {}", file));
+            State::Message
+        }
+        ExCommand::Write(file) => {
+            let name = file.unwrap_or_else(|| content.name().clone());
+            save::save_as(content, view, name)
+        }
+        ExCommand::Quit(forced) => {
+            if content.was_modified() && !forced {
+                return Err("Unsaved changes, add ! to close anyway".to_string());
+            }
+            State::Close
+        }
+        ExCommand::QuitAll => State::QuitAll(Vec::new()),
+        ExCommand::WriteQuit(None, true) if !content.was_modified() => State::Close,
+        ExCommand::WriteQuit(Some(file), _) if is_copy(content, &file) => {
+            save::write_copy(content, &file)?;
+            if content.was_modified() {
+                State::confirm_close(content, view)
+            } else {
+                State::Close
+            }
+        }
+        ExCommand::WriteQuit(file, _) => {
+            let name = file.unwrap_or_else(|| content.name().clone());
+            save::save_then(content, view, name, State::Close)
+        }
+        ExCommand::Edit(file) => State::Open(file),
+        ExCommand::Set(options) => set(view, &options)?,
+        ExCommand::Goto(position) => goto::goto(content, view, &position),
+        ExCommand::Substitute(args) => {
+            let range = range.unwrap_or(Range {
+                first: content.line(),
+                last: content.line(),
+            });
+            substitute(content, view, range, &args)?
+        }
+        ExCommand::Shell(cmd) => shell(content, view, range, &cmd)?,
+        ExCommand::Jump => {
+            let line = range.map_or(content.line(), |range| range.last);
+            goto::goto(content, view, &(line + 1).to_string())
+        }
+    };
+    Ok(state)
+}
+
+// Changes the settings of the buffer, such as "tabwidth=2", "softtabs" or "nonumbers", showing
+// them when none are given
+fn set(view: &mut View, options: &str) -> Result<State, String> {
+    if options.is_empty() {
+        let s = view.settings();
+        let flag = |on: bool, name: &str| format!("{}{}", if on { "" } else { "no" }, name);
+        view.message(&format!(
+            "tabwidth={} scrollspeed={} {} {} {}",
+            s.tab_width,
+            s.scroll_speed,
+            flag(s.soft_tabs, "softtabs"),
+            flag(s.line_numbers, "numbers"),
+            flag(s.auto_indent, "autoindent"),
+        ));
+        return Ok(State::Message);
+    }
+    let overrides = parse_set(options)?;
+    view.quiet();
+    Ok(State::Set(overrides))
+}
+
+fn parse_set(options: &str) -> Result<Overrides, String> {
+    let mut overrides = Overrides::default();
+    for option in options.split_whitespace() {
+        let (name, value) = match option.find('=') {
+            Some(i) => (&option[..i], Some(&option[i + 1..])),
+            None => (option, None),
+        };
+        let number = || match value.map(str::parse::<usize>) {
+            Some(Ok(n)) => Ok(n),
+            _ => Err(format!("{} takes a number", name)),
+        };
+        match (name, value) {
+            ("tabwidth", _) => match number()? {
+                0 => return Err("tabwidth can't be 0".to_string()),
+                n => overrides.tab_width = Some(n),
+            },
+            ("scrollspeed", _) => overrides.scroll_speed = Some(number()?),
+            ("softtabs", None) => overrides.soft_tabs = Some(true),
+            ("nosofttabs", None) => overrides.soft_tabs = Some(false),
+            ("numbers", None) => overrides.line_numbers = Some(true),
+            ("nonumbers", None) => overrides.line_numbers = Some(false),
+            ("autoindent", None) => overrides.auto_indent = Some(true),
+            ("noautoindent", None) => overrides.auto_indent = Some(false),
+            _ => return Err(format!("Unknown option {}", option)),
+        }
+    }
+    Ok(overrides)
+}
+
+// Replaces matches of a regex in the lines of the range, given as "/pattern/replacement/flags"
+// with g for every match of a line and i to ignore case, all of it being undone at once
+fn substitute<T>(
+    content: &mut T,
+    view: &mut View,
+    range: Range,
+    args: &str,
+) -> Result<State, String>
+where
+    T: Editable + Selectable + Undoable,
+{
+    let (regex, replacement, limit) = parse_substitute(args)?;
+
+    content.reset_sel();
+    content.reset_cursors();
+    content.begin_group();
+    let mut count = 0;
+    let mut changed = None;
+    // from the bottom, so that the lines left to change stay where they are
+    for line in (range.first..=range.last).rev() {
+        let text = line_text(content, line);
+        let mut matches = regex.find_iter(&text).count();
+        if limit > 0 {
+            matches = matches.min(limit);
+        }
+        if matches == 0 {
+            continue;
+        }
+        let replaced = regex.replacen(&text, limit, replacement.as_str());
+        replace_lines(content, line, line, &replaced);
+        count += matches;
+        changed = changed.or(Some(line));
+    }
+    content.end_group();
+
+    let last = match changed {
+        Some(line) => line,
+        None => return Err(format!("No match for {}", regex.as_str())),
+    };
+    content.move_at(last, 0);
+    view.adjust_view(content.line());
+    let plural = if count == 1 { "" } else { "s" };
+    view.message(&format!("Replaced {} occurrence{}", count, plural));
+    Ok(State::Message)
+}
+
+// Parses "/pattern/replacement/flags" into the regex, the replacement and how many matches to
+// replace on each line, 0 meaning all of them
+fn parse_substitute(args: &str) -> Result<(Regex, String, usize), String> {
+    let delimiter = match args.chars().next() {
+        Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' => c,
+        _ => return Err("Expected s/pattern/replacement/".to_string()),
+    };
+    let parts = split_escaped(&args[delimiter.len_utf8()..], delimiter);
+    let (pattern, replacement, flags) = match parts.as_slice() {
+        [pattern, replacement] => (pattern, replacement, ""),
+        [pattern, replacement, flags] => (pattern, replacement, flags.as_str()),
+        _ => return Err("Expected s/pattern/replacement/".to_string()),
+    };
+    if let Some(flag) = flags.chars().find(|&c| c != 'g' && c != 'i') {
+        return Err(format!("Unknown flag {}", flag));
+    }
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(flags.contains('i'))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let limit = if flags.contains('g') { 0 } else { 1 };
+    Ok((regex, replacement.clone(), limit))
+}
+
+// Parts of the text between delimiters, which can be escaped with a backslash
+fn split_escaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            },
+            _ if c == delimiter => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+// Runs a shell command, filtering the lines of the range through it if one is given, or
+// showing the first line of its output otherwise
+fn shell<T>(
+    content: &mut T,
+    view: &mut View,
+    range: Option<Range>,
+    cmd: &str,
+) -> Result<State, String>
+where
+    T: Editable + Selectable + Undoable,
+{
+    if cmd.is_empty() {
+        return Err("No command given".to_string());
+    }
+    let input = range.map(|range| {
+        (range.first..=range.last)
+            .map(|line| line_text(content, line) + "\n")
+            .collect::<String>()
+    });
+    let output = run_shell(cmd, input)?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(match error.lines().next() {
+            Some(line) => format!("{}: {}", cmd, line),
+            None => format!("{}: {}", cmd, output.status),
+        });
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let range = match range {
+        Some(range) => range,
+        None => {
+            view.message(stdout.lines().next().unwrap_or(""));
+            return Ok(State::Message);
+        }
+    };
+    content.reset_sel();
+    content.reset_cursors();
+    content.begin_group();
+    replace_lines(
+        content,
+        range.first,
+        range.last,
+        stdout.strip_suffix('\n').unwrap_or(&stdout),
+    );
+    content.end_group();
+    content.move_at(range.first, 0);
+    view.adjust_view(content.line());
+    view.quiet();
+    Ok(State::Insert)
+}
+
+fn run_shell(cmd: &str, input: Option<String>) -> Result<std::process::Output, String> {
+    let mut child = Process::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Can't run {}: {}", cmd, e))?;
+    // written on its own so that a command reading slowly can't block on its output
+    let writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(input)) => Some(thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        })),
+        _ => None,
+    };
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Can't run {}: {}", cmd, e))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    Ok(output)
+}
+
+// Text of a line, without its line break
+fn line_text<T: Editable>(content: &T, line: usize) -> String {
+    let text: String = content.iter_line(line).collect();
+    let text = text.strip_suffix('\n').unwrap_or(&text);
+    text.strip_suffix('\r').unwrap_or(text).to_string()
+}
+
+// Replaces the lines between first and last, both included, with the text
+fn replace_lines<T: Editable>(content: &mut T, first: usize, last: usize, text: &str) {
+    let beg = content.line_index_to_char_index(first);
+    let end = content.line_index_to_char_index(last) + line_text(content, last).chars().count();
+    content.move_to(end);
+    for _ in beg..end {
+        content.delete();
+    }
+    for c in text.chars() {
+        content.insert(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Twenty numbered lines, the cursor on the fifth
+    fn content() -> Text {
+        let mut text = Text::empty();
+        for i in 1..=20 {
+            for c in i.to_string().chars() {
+                text.insert(c);
+            }
+            if i < 20 {
+                text.insert('\n');
+            }
+        }
+        text.move_at(4, 0);
+        text
+    }
+
+    fn range(input: &str) -> Result<Option<(usize, usize)>, String> {
+        let (range, rest) = parse_range(&content(), input)?;
+        assert_eq!(rest, "", "left over from {}", input);
+        Ok(range.map(|range| (range.first, range.last)))
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(range("%"), Ok(Some((0, 19))));
+        assert_eq!(range("."), Ok(Some((4, 4))));
+        assert_eq!(range("$"), Ok(Some((19, 19))));
+        assert_eq!(range("12"), Ok(Some((11, 11))));
+        assert_eq!(range("12,$"), Ok(Some((11, 19))));
+        assert_eq!(range(".,+3"), Ok(Some((4, 7))));
+        assert_eq!(range("$-2,$"), Ok(Some((17, 19))));
+        assert_eq!(range("1+2-1"), Ok(Some((1, 1))));
+        // backwards ranges are turned around
+        assert_eq!(range("5,2"), Ok(Some((1, 4))));
+        assert_eq!(range(""), Ok(None));
+    }
+
+    #[test]
+    fn signs_on_their_own() {
+        assert_eq!(range("+"), Ok(Some((5, 5))));
+        assert_eq!(range("-"), Ok(Some((3, 3))));
+        assert_eq!(range("++"), Ok(Some((6, 6))));
+        assert_eq!(range(".-,.+"), Ok(Some((3, 5))));
+    }
+
+    #[test]
+    fn bad_ranges() {
+        assert!(range("0").is_err());
+        assert!(range("1-1").is_err());
+        assert!(range("21").is_err());
+        assert!(range("$+1").is_err());
+        assert!(range("3,").is_err());
+    }
+
+    #[test]
+    fn range_stops_at_command() {
+        let (range, rest) = parse_range(&content(), "2,4s/a/b/").unwrap();
+        let range = range.unwrap();
+        assert_eq!((range.first, range.last), (1, 3));
+        assert_eq!(rest, "s/a/b/");
+    }
+
+    #[test]
+    fn substitute_escapes_delimiter() {
+        let (regex, replacement, limit) = parse_substitute("/a\\/b/c\\/d/").unwrap();
+        assert_eq!(regex.as_str(), "a/b");
+        assert_eq!(replacement, "c/d");
+        assert_eq!(limit, 1);
+
+        // other escapes are left to the regex
+        let (regex, replacement, _) = parse_substitute("#\\d\\##x#").unwrap();
+        assert_eq!(regex.as_str(), "\\d#");
+        assert_eq!(replacement, "x");
+
+        let (regex, replacement, _) = parse_substitute("/a//").unwrap();
+        assert_eq!(regex.as_str(), "a");
+        assert_eq!(replacement, "");
+    }
+
+    #[test]
+    fn substitute_flags() {
+        let (regex, _, limit) = parse_substitute("/a/b").unwrap();
+        assert_eq!(limit, 1);
+        assert!(!regex.is_match("A"));
+
+        let (regex, _, limit) = parse_substitute("/a/b/gi").unwrap();
+        assert_eq!(limit, 0);
+        assert!(regex.is_match("A"));
+
+        assert_eq!(
+            parse_substitute("/a/b/x").unwrap_err(),
+            "Unknown flag x".to_string()
+        );
+    }
+
+    #[test]
+    fn bad_substitutes() {
+        assert!(parse_substitute("").is_err());
+        assert!(parse_substitute("/a").is_err());
+        assert!(parse_substitute("xaxbx").is_err());
+        assert!(parse_substitute("/a/b/g/").is_err());
+        assert!(parse_substitute("/(/b/").is_err());
+    }
+
+    #[test]
+    fn split_on_unescaped_delimiter() {
+        assert_eq!(split_escaped("a/b", '/'), vec!["a", "b"]);
+        assert_eq!(split_escaped("a\\/b/", '/'), vec!["a/b", ""]);
+        assert_eq!(split_escaped("a\\n\\", '/'), vec!["a\\n\\"]);
+    }
+
+    #[test]
+    fn commands() {
+        let text = content();
+        assert!(matches!(
+            parse(&text, "q"),
+            Ok((None, ExCommand::Quit(false)))
+        ));
+        assert!(matches!(
+            parse(&text, "q!"),
+            Ok((None, ExCommand::Quit(true)))
+        ));
+        assert!(matches!(
+            parse(&text, "quit!"),
+            Ok((None, ExCommand::Quit(true)))
+        ));
+        assert!(matches!(parse(&text, "qa"), Ok((None, ExCommand::QuitAll))));
+        assert!(matches!(
+            parse(&text, "w"),
+            Ok((None, ExCommand::Write(None)))
+        ));
+        assert!(matches!(
+            parse(&text, "w a.txt"),
+            Ok((None, ExCommand::Write(Some(ref file)))) if file == "a.txt"
+        ));
+        assert!(matches!(
+            parse(&text, "x"),
+            Ok((None, ExCommand::WriteQuit(None, true)))
+        ));
+        assert!(matches!(
+            parse(&text, "%s/a/b/"),
+            Ok((Some(Range { first: 0, last: 19 }), ExCommand::Substitute(ref args)))
+                if args == "/a/b/"
+        ));
+        assert!(matches!(
+            parse(&text, "1,3 !sort"),
+            Ok((Some(Range { first: 0, last: 2 }), ExCommand::Shell(ref cmd))) if cmd == "sort"
+        ));
+        assert!(matches!(
+            parse(&text, "$"),
+            Ok((Some(Range { last: 19, .. }), ExCommand::Jump))
+        ));
+    }
+
+    #[test]
+    fn bad_commands() {
+        let text = content();
+        assert!(parse(&text, "").is_err());
+        assert!(parse(&text, "qx").is_err());
+        assert!(parse(&text, "q file").is_err());
+        assert!(parse(&text, "q?").is_err());
+        assert!(parse(&text, "e").is_err());
+    }
+
+    #[test]
+    fn ranges_only_where_taken() {
+        let text = content();
+        for input in &[
+            "1,2w",
+            "%q",
+            ".q!",
+            "3qa",
+            "%wq",
+            "1e file",
+            "2set numbers",
+            "%goto 3",
+        ] {
+            assert!(
+                parse(&text, input)
+                    .unwrap_err()
+                    .starts_with("No range allowed"),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn completes_commands_and_options() {
+        assert_eq!(candidates("se"), vec!["set"]);
+        assert_eq!(candidates("w"), vec!["wq", "write"]);
+        assert_eq!(candidates("set tab"), vec!["set tabwidth="]);
+        assert_eq!(candidates("set nos"), vec!["set nosofttabs"]);
+        assert_eq!(candidates("set softtabs n"), vec!["set softtabs numbers"]);
+        // options taking a value can't be turned off
+        assert!(candidates("set notab").is_empty());
+        assert!(candidates("goto 1").is_empty());
+    }
+
+    #[test]
+    fn parses_options() {
+        let overrides = parse_set("tabwidth=2 nosofttabs numbers").unwrap();
+        assert_eq!(overrides.tab_width, Some(2));
+        assert_eq!(overrides.soft_tabs, Some(false));
+        assert_eq!(overrides.line_numbers, Some(true));
+        // what isn't set is left to the config
+        assert_eq!(overrides.auto_indent, None);
+        assert_eq!(overrides.scroll_speed, None);
+
+        assert_eq!(parse_set("tabwidth=0").unwrap_err(), "tabwidth can't be 0");
+        assert_eq!(
+            parse_set("scrollspeed=fast").unwrap_err(),
+            "scrollspeed takes a number"
+        );
+        assert_eq!(
+            parse_set("softtabs=1").unwrap_err(),
+            "Unknown option softtabs=1"
+        );
+    }
+}
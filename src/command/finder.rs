@@ -0,0 +1,45 @@
+use super::picker::{PickAction, Picker};
+use super::State;
+use crate::view::View;
+use ignore::WalkBuilder;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// Walking stops there so that opening a file from a huge tree stays snappy
+const MAX_FILES: usize = 10000;
+const PREVIEW_LINES: usize = 100;
+
+// Lets the user pick one of the files of the project to open
+pub(super) fn start_finder(view: &mut View) -> State {
+    Picker::new(project_files(), 0, PickAction::File).show(view)
+}
+
+// Paths of the files under the current directory, leaving out hidden and ignored ones
+fn project_files() -> Vec<String> {
+    let mut files: Vec<String> = WalkBuilder::new(".")
+        .require_git(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .filter_map(|entry| {
+            let path = entry
+                .path()
+                .strip_prefix(".")
+                .unwrap_or_else(|_| entry.path());
+            path.to_str().map(String::from)
+        })
+        .take(MAX_FILES)
+        .collect();
+    files.sort();
+    files
+}
+
+// First lines of a file, if it can be read as text
+pub(super) fn preview(path: &str) -> Option<Vec<String>> {
+    let reader = BufReader::new(File::open(path).ok()?);
+    reader
+        .lines()
+        .take(PREVIEW_LINES)
+        .collect::<Result<_, _>>()
+        .ok()
+}
@@ -0,0 +1,78 @@
+use super::line_edit::LineEdit;
+use super::{PromptAction, State};
+use crate::data::*;
+use crate::view::View;
+
+pub(super) fn start_goto(view: &mut View) -> State {
+    let prompt = "Go to line[:col] or +/-lines: ".to_string();
+    view.prompt(&prompt, "");
+    State::Prompt(prompt, LineEdit::default(), PromptAction::Goto)
+}
+
+pub(super) fn goto<T>(content: &mut T, view: &mut View, input: &str) -> State
+where
+    T: Editable + Selectable,
+{
+    match parse(input.trim(), content.line()) {
+        Some((line, col)) => {
+            content.reset_sel();
+            content.reset_cursors();
+            content.move_at(line, col);
+            view.center_view(content.line());
+            view.quiet();
+            State::Insert
+        }
+        None => {
+            view.message(&format!("Invalid position {}", input));
+            State::Message
+        }
+    }
+}
+
+// Parses "line", "line:col" (both starting at 1) or a number of lines to move by from the
+// current one, such as "+10" or "-3"
+fn parse(input: &str, current: usize) -> Option<(usize, usize)> {
+    if let Some(lines) = input.strip_prefix('+') {
+        return Some((current + lines.parse::<usize>().ok()?, 0));
+    }
+    if let Some(lines) = input.strip_prefix('-') {
+        return Some((current.saturating_sub(lines.parse().ok()?), 0));
+    }
+    let mut parts = input.splitn(2, ':');
+    let line: usize = parts.next()?.parse().ok()?;
+    let col: usize = match parts.next() {
+        Some(col) => col.parse().ok()?,
+        None => 1,
+    };
+    Some((line.saturating_sub(1), col.saturating_sub(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_and_col() {
+        assert_eq!(parse("12", 4), Some((11, 0)));
+        assert_eq!(parse("12:5", 4), Some((11, 4)));
+        assert_eq!(parse("0", 4), Some((0, 0)));
+        assert_eq!(parse("0:0", 4), Some((0, 0)));
+    }
+
+    #[test]
+    fn relative_lines() {
+        assert_eq!(parse("+3", 4), Some((7, 0)));
+        assert_eq!(parse("-3", 4), Some((1, 0)));
+        assert_eq!(parse("-10", 4), Some((0, 0)));
+        assert_eq!(parse("+0", 4), Some((4, 0)));
+    }
+
+    #[test]
+    fn invalid_positions() {
+        assert_eq!(parse("", 4), None);
+        assert_eq!(parse("+", 4), None);
+        assert_eq!(parse("12:", 4), None);
+        assert_eq!(parse("1:2:3", 4), None);
+        assert_eq!(parse("x", 4), None);
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+const HISTORY_SIZE: usize = 64;
+
+/// Entries previously submitted to a prompt, the most recent one first
+pub struct History {
+    entries: VecDeque<String>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: String) {
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| *e != entry);
+        self.entries.push_front(entry);
+        self.entries.truncate(HISTORY_SIZE);
+    }
+
+    // Entry submitted before current, or the most recent one if current isn't from the history
+    pub fn older(&self, current: &str) -> Option<&String> {
+        match self.position(current) {
+            Some(i) => self.entries.get(i + 1),
+            None => self.entries.front(),
+        }
+    }
+
+    // Entry submitted after current, the most recent one being followed by an empty entry
+    pub fn newer(&self, current: &str) -> Option<String> {
+        match self.position(current) {
+            Some(0) => Some(String::new()),
+            Some(i) => self.entries.get(i - 1).cloned(),
+            None => None,
+        }
+    }
+
+    fn position(&self, current: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e == current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> History {
+        let mut history = History::new();
+        for entry in entries {
+            history.push(entry.to_string());
+        }
+        history
+    }
+
+    #[test]
+    fn most_recent_first_without_duplicates() {
+        let history = history(&["a", "b", "", "a"]);
+        assert_eq!(history.entries, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn keeps_the_last_entries() {
+        let mut history = History::new();
+        for i in 0..HISTORY_SIZE + 5 {
+            history.push(i.to_string());
+        }
+        assert_eq!(history.entries.len(), HISTORY_SIZE);
+        assert_eq!(history.entries[0], (HISTORY_SIZE + 4).to_string());
+    }
+
+    #[test]
+    fn walks_back_and_forth() {
+        let history = history(&["a", "b", "c"]);
+        // typed text starts from the most recent entry
+        assert_eq!(history.older("typed"), Some(&"c".to_string()));
+        assert_eq!(history.older("c"), Some(&"b".to_string()));
+        assert_eq!(history.older("a"), None);
+        assert_eq!(history.newer("a"), Some("b".to_string()));
+        assert_eq!(history.newer("c"), Some(String::new()));
+        assert_eq!(history.newer("typed"), None);
+    }
+
+    #[test]
+    fn empty_history() {
+        let history = History::new();
+        assert_eq!(history.older(""), None);
+        assert_eq!(history.newer(""), None);
+    }
+}
@@ -0,0 +1,89 @@
+use termion::event::{Key, MouseButton, MouseEvent};
+
+// sequences surrounding pasted text when the terminal is in bracketed paste mode
+pub const PASTE_START: &[u8] = b"\x1b[200~";
+pub const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Modifier keys held down along with a special key
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    // xterm encodes modifiers as 1 + a bitmask of shift, alt and ctrl
+    fn from_xterm(code: u8) -> Self {
+        let mask = code.saturating_sub(1);
+        Modifiers {
+            shift: mask & 1 != 0,
+            alt: mask & 2 != 0,
+            ctrl: mask & 4 != 0,
+        }
+    }
+}
+
+// Decodes the xterm sequences for modified special keys (e.g. Shift+Left is "\x1b[1;2D"),
+// which termion doesn't know about and reports as unsupported
+pub fn parse_modified_key(seq: &[u8]) -> Option<(Key, Modifiers)> {
+    if seq.len() < 3 || seq[0] != b'\x1b' || seq[1] != b'[' {
+        return None;
+    }
+    let (last, params) = seq[2..].split_last()?;
+    let params = std::str::from_utf8(params).ok()?;
+    let mut params = params.split(';');
+    let code: u8 = params.next()?.parse().ok()?;
+    let modifiers: u8 = params.next()?.parse().ok()?;
+    if params.next().is_some() {
+        return None;
+    }
+
+    let key = match (last, code) {
+        (b'A', 1) => Key::Up,
+        (b'B', 1) => Key::Down,
+        (b'C', 1) => Key::Right,
+        (b'D', 1) => Key::Left,
+        (b'H', 1) => Key::Home,
+        (b'F', 1) => Key::End,
+        (b'R', 1) => Key::F(3),
+        (b'~', 1) | (b'~', 7) => Key::Home,
+        (b'~', 3) => Key::Delete,
+        (b'~', 4) | (b'~', 8) => Key::End,
+        (b'~', 5) => Key::PageUp,
+        (b'~', 6) => Key::PageDown,
+        _ => return None,
+    };
+    Some((key, Modifiers::from_xterm(modifiers)))
+}
+
+// Decodes SGR mouse reports carrying modifiers (e.g. Alt+click), which termion reports as
+// unsupported
+pub fn parse_modified_mouse(seq: &[u8]) -> Option<(MouseEvent, Modifiers)> {
+    if !seq.starts_with(b"\x1b[<") {
+        return None;
+    }
+    let (last, params) = seq[3..].split_last()?;
+    let params = std::str::from_utf8(params).ok()?;
+    let mut params = params.split(';').map(|n| n.parse::<u16>());
+    let cb = params.next()?.ok()?;
+    let cx = params.next()?.ok()?;
+    let cy = params.next()?.ok()?;
+
+    let modifiers = Modifiers {
+        shift: cb & 4 != 0,
+        alt: cb & 8 != 0,
+        ctrl: cb & 16 != 0,
+    };
+    let event = match (last, cb & 0b110_0011) {
+        (b'm', _) => MouseEvent::Release(cx, cy),
+        (b'M', _) if cb & 32 != 0 => MouseEvent::Hold(cx, cy),
+        (b'M', 0) => MouseEvent::Press(MouseButton::Left, cx, cy),
+        (b'M', 1) => MouseEvent::Press(MouseButton::Middle, cx, cy),
+        (b'M', 2) => MouseEvent::Press(MouseButton::Right, cx, cy),
+        (b'M', 64) => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
+        (b'M', 65) => MouseEvent::Press(MouseButton::WheelDown, cx, cy),
+        _ => return None,
+    };
+    Some((event, modifiers))
+}
@@ -0,0 +1,700 @@
+use super::{add_next_occurrence, paste, select_all, split_sel_lines};
+use super::{ex, finder, goto, replace, save, search};
+use super::{Context, PaneAction, PromptAction, State};
+use crate::data::*;
+use crate::view::View;
+use std::collections::HashMap;
+use termion::event::{Event, Key};
+
+/// Something that can be done from the keyboard, bound to keys by its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Save,
+    SaveAs,
+    Rename,
+    Open,
+    OpenRecent,
+    Close,
+    QuitAll,
+    ListBuffers,
+    NextBuffer,
+    PreviousBuffer,
+    SplitStacked,
+    SplitSideBySide,
+    ClosePane,
+    GrowPane,
+    ShrinkPane,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    SelectAll,
+    AddNextOccurrence,
+    SplitSelection,
+    FlipSelection,
+    Find,
+    FindNext,
+    Replace,
+    GotoLine,
+    NextOccurrence,
+    PreviousOccurrence,
+    Palette,
+    Ex,
+}
+
+const COMMANDS: &[(Command, &str)] = &[
+    (Command::Save, "save"),
+    (Command::SaveAs, "save-as"),
+    (Command::Rename, "rename"),
+    (Command::Open, "open"),
+    (Command::OpenRecent, "open-recent"),
+    (Command::Close, "close"),
+    (Command::QuitAll, "quit-all"),
+    (Command::ListBuffers, "list-buffers"),
+    (Command::NextBuffer, "next-buffer"),
+    (Command::PreviousBuffer, "previous-buffer"),
+    (Command::SplitStacked, "split-stacked"),
+    (Command::SplitSideBySide, "split-side-by-side"),
+    (Command::ClosePane, "close-pane"),
+    (Command::GrowPane, "grow-pane"),
+    (Command::ShrinkPane, "shrink-pane"),
+    (Command::Undo, "undo"),
+    (Command::Redo, "redo"),
+    (Command::Copy, "copy"),
+    (Command::Cut, "cut"),
+    (Command::Paste, "paste"),
+    (Command::SelectAll, "select-all"),
+    (Command::AddNextOccurrence, "add-next-occurrence"),
+    (Command::SplitSelection, "split-selection"),
+    (Command::FlipSelection, "flip-selection"),
+    (Command::Find, "find"),
+    (Command::FindNext, "find-next"),
+    (Command::Replace, "replace"),
+    (Command::GotoLine, "goto-line"),
+    (Command::NextOccurrence, "next-occurrence"),
+    (Command::PreviousOccurrence, "previous-occurrence"),
+    (Command::Palette, "command-palette"),
+    (Command::Ex, "command-line"),
+];
+
+const DEFAULT_BINDINGS: &[(&str, Command)] = &[
+    ("ctrl-s", Command::Save),
+    ("alt-S", Command::SaveAs),
+    ("alt-R", Command::Rename),
+    ("ctrl-o", Command::Open),
+    ("alt-r", Command::OpenRecent),
+    ("ctrl-q", Command::Close),
+    ("alt-q", Command::QuitAll),
+    ("ctrl-b", Command::ListBuffers),
+    ("alt-.", Command::NextBuffer),
+    ("alt-,", Command::PreviousBuffer),
+    ("alt-s", Command::SplitStacked),
+    ("alt-v", Command::SplitSideBySide),
+    ("alt-w", Command::ClosePane),
+    ("alt-=", Command::GrowPane),
+    ("alt-+", Command::GrowPane),
+    ("alt--", Command::ShrinkPane),
+    ("ctrl-z", Command::Undo),
+    ("ctrl-y", Command::Redo),
+    ("ctrl-c", Command::Copy),
+    ("ctrl-x", Command::Cut),
+    ("ctrl-v", Command::Paste),
+    ("ctrl-a", Command::SelectAll),
+    ("ctrl-d", Command::AddNextOccurrence),
+    ("alt-l", Command::SplitSelection),
+    ("alt-o", Command::FlipSelection),
+    ("ctrl-f", Command::Find),
+    ("f3", Command::FindNext),
+    ("ctrl-r", Command::Replace),
+    ("ctrl-g", Command::GotoLine),
+    ("alt-n", Command::NextOccurrence),
+    ("alt-p", Command::PreviousOccurrence),
+    ("ctrl-p", Command::Palette),
+    ("ctrl-e", Command::Ex),
+];
+
+/// Something that can be done in a prompt besides editing its line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptCommand {
+    Accept,
+    Cancel,
+    Complete,
+    CompleteBackward,
+    Paste,
+    HistoryOlder,
+    HistoryNewer,
+}
+
+const PROMPT_COMMANDS: &[(PromptCommand, &str)] = &[
+    (PromptCommand::Accept, "accept"),
+    (PromptCommand::Cancel, "cancel"),
+    (PromptCommand::Complete, "complete"),
+    (PromptCommand::CompleteBackward, "complete-backward"),
+    (PromptCommand::Paste, "paste"),
+    (PromptCommand::HistoryOlder, "history-older"),
+    (PromptCommand::HistoryNewer, "history-newer"),
+];
+
+const DEFAULT_PROMPT_BINDINGS: &[(&str, PromptCommand)] = &[
+    ("enter", PromptCommand::Accept),
+    ("esc", PromptCommand::Cancel),
+    ("ctrl-q", PromptCommand::Cancel),
+    ("tab", PromptCommand::Complete),
+    ("shift-tab", PromptCommand::CompleteBackward),
+    ("ctrl-v", PromptCommand::Paste),
+    ("up", PromptCommand::HistoryOlder),
+    ("down", PromptCommand::HistoryNewer),
+];
+
+impl Command {
+    // Every command, in the order they are listed
+    pub fn all() -> impl Iterator<Item = Command> {
+        COMMANDS.iter().map(|&(command, _)| command)
+    }
+
+    pub fn name(self) -> &'static str {
+        COMMANDS.iter().find(|&&(c, _)| c == self).unwrap().1
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        COMMANDS.iter().find(|&&(_, n)| n == name).map(|&(c, _)| c)
+    }
+
+    // Whether the command acts on the selection rather than dropping it
+    fn keeps_selection(self) -> bool {
+        matches!(
+            self,
+            Command::Copy
+                | Command::Cut
+                | Command::AddNextOccurrence
+                | Command::SplitSelection
+                | Command::FlipSelection
+                | Command::FindNext
+                | Command::Replace
+                | Command::NextOccurrence
+                | Command::PreviousOccurrence
+                | Command::Palette
+        )
+    }
+}
+
+/// Sequences of keys bound to commands, and keys bound to the commands of prompts
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Key>, Command>,
+    prompt_bindings: HashMap<Key, PromptCommand>,
+}
+
+// What a sequence of keys typed so far amounts to
+pub enum Lookup {
+    Command(Command),
+    // the start of longer sequences
+    Prefix,
+    Unbound,
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .map(|&(keys, command)| (parse_keys(keys).unwrap(), command))
+            .collect();
+        let prompt_bindings = DEFAULT_PROMPT_BINDINGS
+            .iter()
+            .map(|&(key, command)| (parse_key(key).unwrap(), command))
+            .collect();
+        Keymap {
+            bindings,
+            prompt_bindings,
+        }
+    }
+
+    // Binds keys such as "ctrl-k ctrl-c" to the command of the given name, or unbinds them if it
+    // is "none", refusing sequences which would never be reached or hide longer ones
+    pub fn bind(&mut self, keys: &str, command: &str) -> Result<(), String> {
+        let keys = parse_keys(keys)?;
+        if command == "none" {
+            self.bindings.remove(&keys);
+            return Ok(());
+        }
+        let command =
+            Command::from_name(command).ok_or_else(|| format!("Unknown command {}", command))?;
+        for len in 1..keys.len() {
+            if let Some(bound) = self.bindings.get(&keys[..len]) {
+                return Err(format!(
+                    "{} is already bound to {}",
+                    describe_keys(&keys[..len]),
+                    bound.name()
+                ));
+            }
+        }
+        if let Some(longer) = self
+            .bindings
+            .keys()
+            .find(|bound| bound.len() > keys.len() && bound.starts_with(&keys))
+        {
+            return Err(format!(
+                "{} starts the sequence {}",
+                describe_keys(&keys),
+                describe_keys(longer)
+            ));
+        }
+        self.bindings.insert(keys, command);
+        Ok(())
+    }
+
+    // Binds a single key to the prompt command of the given name, or unbinds it if it is "none"
+    pub fn bind_prompt(&mut self, key: &str, command: &str) -> Result<(), String> {
+        let key = match parse_keys(key)?[..] {
+            [key] => key,
+            _ => return Err("Prompts don't take sequences of keys".to_string()),
+        };
+        if command == "none" {
+            self.prompt_bindings.remove(&key);
+            return Ok(());
+        }
+        let command = PROMPT_COMMANDS
+            .iter()
+            .find(|&&(_, n)| n == command)
+            .map(|&(c, _)| c)
+            .ok_or_else(|| format!("Unknown prompt command {}", command))?;
+        self.prompt_bindings.insert(key, command);
+        Ok(())
+    }
+
+    pub fn lookup_prompt(&self, key: Key) -> Option<PromptCommand> {
+        self.prompt_bindings.get(&key).cloned()
+    }
+
+    // Keys bound to a command, as they are written in messages
+    pub fn keys_for(&self, command: Command) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|&(_, &c)| c == command)
+            .map(|(keys, _)| describe_keys(keys))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn lookup(&self, keys: &[Key]) -> Lookup {
+        if let Some(&command) = self.bindings.get(keys) {
+            return Lookup::Command(command);
+        }
+        if self
+            .bindings
+            .keys()
+            .any(|bound| bound.len() > keys.len() && bound.starts_with(keys))
+        {
+            Lookup::Prefix
+        } else {
+            Lookup::Unbound
+        }
+    }
+}
+
+// Keys going by their name, written as they are in messages
+const NAMED_KEYS: &[(&str, Key)] = &[
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Esc", Key::Esc),
+    ("Enter", Key::Char('\n')),
+    ("Tab", Key::Char('\t')),
+    ("Shift-Tab", Key::BackTab),
+    ("Backspace", Key::Backspace),
+    ("Delete", Key::Delete),
+    ("Insert", Key::Insert),
+];
+
+// Parses keys separated by spaces, such as "ctrl-s", "alt-S" or "ctrl-k ctrl-c"
+fn parse_keys(keys: &str) -> Result<Vec<Key>, String> {
+    let keys: Vec<Key> = keys
+        .split_whitespace()
+        .map(|key| parse_key(key).ok_or_else(|| format!("Unknown key {}", key)))
+        .collect::<Result<_, _>>()?;
+    if keys.is_empty() {
+        return Err("No key to bind".to_string());
+    }
+    Ok(keys)
+}
+
+fn parse_key(key: &str) -> Option<Key> {
+    let named = NAMED_KEYS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key));
+    if let Some(&(_, key)) = named {
+        return Some(key);
+    }
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(c) = key.strip_prefix("ctrl-").and_then(single) {
+        return Some(Key::Ctrl(c.to_ascii_lowercase()));
+    }
+    if let Some(c) = key.strip_prefix("alt-").and_then(single) {
+        return Some(Key::Alt(c));
+    }
+    if let Some(n) = key.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        return Some(Key::F(n));
+    }
+    single(key).map(Key::Char)
+}
+
+// How keys are written in messages, e.g. "Ctrl-K Ctrl-C"
+pub fn describe_keys(keys: &[Key]) -> String {
+    keys.iter()
+        .map(|&key| match key {
+            Key::Ctrl(c) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+            Key::Alt(c) if c.is_uppercase() => format!("Alt-Shift-{}", c),
+            Key::Alt(c) => format!("Alt-{}", c.to_ascii_uppercase()),
+            Key::F(n) => format!("F{}", n),
+            key => match NAMED_KEYS.iter().find(|&&(_, k)| k == key) {
+                Some((name, _)) => name.to_string(),
+                None => match key {
+                    Key::Char(c) => c.to_string(),
+                    key => format!("{:?}", key),
+                },
+            },
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl State {
+    // Waits for the rest of a sequence of keys, the first of which were typed
+    pub(super) fn handle_chord<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        mut keys: Vec<Key>,
+    ) -> Self
+    where
+        T: Editable + Named + Undoable + Modifiable + Saveable + Selectable,
+    {
+        let key = match event {
+            Event::Key(Key::Esc) => {
+                content.reset_sel();
+                view.quiet();
+                return State::Insert;
+            }
+            Event::Key(key) => key,
+            _ => return State::Chord(keys),
+        };
+        keys.push(key);
+        match ctx.keymap.lookup(&keys) {
+            Lookup::Command(command) => {
+                view.quiet();
+                State::run_command(command, content, view, ctx)
+            }
+            Lookup::Prefix => State::start_chord(view, keys),
+            Lookup::Unbound => {
+                content.reset_sel();
+                view.message(&format!("{} is not bound", describe_keys(&keys)));
+                State::Message
+            }
+        }
+    }
+
+    pub(super) fn start_chord(view: &mut View, keys: Vec<Key>) -> Self {
+        view.message(&format!("{} -", describe_keys(&keys)));
+        State::Chord(keys)
+    }
+
+    // Runs a command, on the selection if it acts on one
+    pub(super) fn run_command<T>(
+        command: Command,
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+    ) -> Self
+    where
+        T: Editable + Named + Undoable + Modifiable + Saveable + Selectable,
+    {
+        if !command.keeps_selection() {
+            content.reset_sel();
+        }
+        let selected = content.sel().is_some();
+        match command {
+            Command::Save => {
+                if content.name().is_empty() {
+                    return save::start_save_as(content, view, PromptAction::Save);
+                }
+                let msg = match content.save() {
+                    Err(e) => e.to_string(),
+                    Ok(_) => format!("Saved file {}", content.name()),
+                };
+                view.message(&msg);
+                State::Message
+            }
+            Command::SaveAs => save::start_save_as(content, view, PromptAction::SaveAs),
+            Command::Rename => save::start_save_as(content, view, PromptAction::Rename),
+            Command::Open => finder::start_finder(view),
+            Command::OpenRecent => State::ListRecent,
+            Command::Close => {
+                if content.was_modified() {
+                    State::confirm_close(content, view)
+                } else {
+                    State::Close
+                }
+            }
+            Command::QuitAll => State::QuitAll(Vec::new()),
+            Command::ListBuffers => State::ListBuffers,
+            Command::NextBuffer => State::CycleBuffer(1),
+            Command::PreviousBuffer => State::CycleBuffer(-1),
+            Command::SplitStacked => State::Pane(PaneAction::Split(false)),
+            Command::SplitSideBySide => State::Pane(PaneAction::Split(true)),
+            Command::ClosePane => State::Pane(PaneAction::Close),
+            Command::GrowPane => State::Pane(PaneAction::Resize(1)),
+            Command::ShrinkPane => State::Pane(PaneAction::Resize(-1)),
+            Command::Undo => {
+                content.undo();
+                State::Insert
+            }
+            Command::Redo => {
+                content.redo();
+                State::Insert
+            }
+            Command::Copy if selected => {
+                ctx.clipboard.copy(view, content.sel_text());
+                State::Selected
+            }
+            Command::Cut if selected => {
+                ctx.clipboard.copy(view, content.sel_text());
+                content.delete_sel();
+                view.adjust_view(content.line());
+                content.reset_sel();
+                State::Insert
+            }
+            Command::Paste => {
+                let len = paste(content, &mut ctx.clipboard);
+                view.adjust_view(content.line());
+                State::Pasted(len, 0)
+            }
+            Command::SelectAll => {
+                content.reset_cursors();
+                select_all(content)
+            }
+            Command::AddNextOccurrence => add_next_occurrence(content, view),
+            Command::SplitSelection if selected => split_sel_lines(content, view),
+            Command::FlipSelection if selected => {
+                // swap ends so that the selection can be extended from the other side
+                let sel = content.sel().unwrap().flipped();
+                content.move_to(sel.head);
+                view.adjust_view(content.line());
+                content.set_sel(sel);
+                State::Selected
+            }
+            Command::Find => search::start_search(content, view, ctx),
+            Command::FindNext => search::repeat_search(content, view, ctx, true),
+            Command::Replace => replace::start_replace(content, view),
+            Command::GotoLine => goto::start_goto(view),
+            Command::NextOccurrence => search::goto_occurrence(content, view, true),
+            Command::PreviousOccurrence => search::goto_occurrence(content, view, false),
+            Command::Palette => State::pick_command(view, &ctx.keymap),
+            Command::Ex => ex::start_command_line(view, ""),
+            Command::Copy | Command::Cut | Command::SplitSelection | Command::FlipSelection => {
+                State::Insert
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_modifiers() {
+        assert_eq!(parse_key("ctrl-s"), Some(Key::Ctrl('s')));
+        // ctrl can't tell upper case from lower case
+        assert_eq!(parse_key("ctrl-S"), Some(Key::Ctrl('s')));
+        assert_eq!(parse_key("alt-s"), Some(Key::Alt('s')));
+        assert_eq!(parse_key("alt-S"), Some(Key::Alt('S')));
+        assert_eq!(parse_key("alt-,"), Some(Key::Alt(',')));
+        assert_eq!(parse_key("ctrl-"), None);
+        assert_eq!(parse_key("ctrl-ab"), None);
+        assert_eq!(parse_key("shift-a"), None);
+    }
+
+    #[test]
+    fn parses_function_keys_and_chars() {
+        assert_eq!(parse_key("f1"), Some(Key::F(1)));
+        assert_eq!(parse_key("f12"), Some(Key::F(12)));
+        assert_eq!(parse_key("f"), Some(Key::Char('f')));
+        assert_eq!(parse_key("fx"), None);
+        assert_eq!(parse_key("x"), Some(Key::Char('x')));
+    }
+
+    #[test]
+    fn parses_named_keys() {
+        assert_eq!(parse_key("up"), Some(Key::Up));
+        assert_eq!(parse_key("PageDown"), Some(Key::PageDown));
+        assert_eq!(parse_key("pageup"), Some(Key::PageUp));
+        assert_eq!(parse_key("home"), Some(Key::Home));
+        assert_eq!(parse_key("end"), Some(Key::End));
+        assert_eq!(parse_key("esc"), Some(Key::Esc));
+        assert_eq!(parse_key("enter"), Some(Key::Char('\n')));
+        assert_eq!(parse_key("tab"), Some(Key::Char('\t')));
+        assert_eq!(parse_key("shift-tab"), Some(Key::BackTab));
+        assert_eq!(parse_key("escape"), None);
+    }
+
+    #[test]
+    fn parses_sequences() {
+        assert_eq!(
+            parse_keys("ctrl-k  ctrl-c"),
+            Ok(vec![Key::Ctrl('k'), Key::Ctrl('c')])
+        );
+        assert_eq!(parse_keys("f5"), Ok(vec![Key::F(5)]));
+        assert_eq!(
+            parse_keys("ctrl-k nope"),
+            Err("Unknown key nope".to_string())
+        );
+        assert!(parse_keys(" ").is_err());
+    }
+
+    #[test]
+    fn describes_keys() {
+        let keys = parse_keys("ctrl-k alt-S alt-s f2 x").unwrap();
+        assert_eq!(describe_keys(&keys), "Ctrl-K Alt-Shift-S Alt-S F2 x");
+        let keys = parse_keys("esc enter tab shift-tab pagedown left").unwrap();
+        assert_eq!(
+            describe_keys(&keys),
+            "Esc Enter Tab Shift-Tab PageDown Left"
+        );
+    }
+
+    #[test]
+    fn lookup_defaults() {
+        let keymap = Keymap::new();
+        assert!(matches!(
+            keymap.lookup(&[Key::Ctrl('s')]),
+            Lookup::Command(Command::Save)
+        ));
+        assert!(matches!(
+            keymap.lookup(&[Key::Alt('S')]),
+            Lookup::Command(Command::SaveAs)
+        ));
+        assert!(matches!(keymap.lookup(&[Key::Ctrl('k')]), Lookup::Unbound));
+    }
+
+    #[test]
+    fn lookup_sequences() {
+        let mut keymap = Keymap::new();
+        keymap.bind("ctrl-k ctrl-c", "copy").unwrap();
+        keymap.bind("ctrl-k ctrl-k ctrl-u", "cut").unwrap();
+        assert!(matches!(keymap.lookup(&[Key::Ctrl('k')]), Lookup::Prefix));
+        assert!(matches!(
+            keymap.lookup(&[Key::Ctrl('k'), Key::Ctrl('c')]),
+            Lookup::Command(Command::Copy)
+        ));
+        assert!(matches!(
+            keymap.lookup(&[Key::Ctrl('k'), Key::Ctrl('k')]),
+            Lookup::Prefix
+        ));
+        assert!(matches!(
+            keymap.lookup(&[Key::Ctrl('k'), Key::Ctrl('x')]),
+            Lookup::Unbound
+        ));
+        assert_eq!(
+            keymap.keys_for(Command::Copy),
+            vec!["Ctrl-C", "Ctrl-K Ctrl-C"]
+        );
+    }
+
+    #[test]
+    fn refuses_hidden_sequences() {
+        let mut keymap = Keymap::new();
+        assert_eq!(
+            keymap.bind("ctrl-c ctrl-c", "copy"),
+            Err("Ctrl-C is already bound to copy".to_string())
+        );
+        keymap.bind("ctrl-k ctrl-c", "copy").unwrap();
+        assert_eq!(
+            keymap.bind("ctrl-k", "cut"),
+            Err("Ctrl-K starts the sequence Ctrl-K Ctrl-C".to_string())
+        );
+        assert!(matches!(keymap.lookup(&[Key::Ctrl('k')]), Lookup::Prefix));
+
+        // once unbound, the key can start sequences
+        keymap.bind("ctrl-c", "none").unwrap();
+        keymap.bind("ctrl-c ctrl-c", "copy").unwrap();
+        assert!(matches!(keymap.lookup(&[Key::Ctrl('c')]), Lookup::Prefix));
+    }
+
+    #[test]
+    fn unbinds_with_none() {
+        let mut keymap = Keymap::new();
+        keymap.bind("ctrl-s", "none").unwrap();
+        assert!(matches!(keymap.lookup(&[Key::Ctrl('s')]), Lookup::Unbound));
+        assert!(keymap.keys_for(Command::Save).is_empty());
+
+        keymap.bind("ctrl-k ctrl-c", "copy").unwrap();
+        keymap.bind("ctrl-k ctrl-c", "none").unwrap();
+        assert!(matches!(keymap.lookup(&[Key::Ctrl('k')]), Lookup::Unbound));
+    }
+
+    #[test]
+    fn rebinds() {
+        let mut keymap = Keymap::new();
+        keymap.bind("ctrl-s", "save-as").unwrap();
+        assert!(matches!(
+            keymap.lookup(&[Key::Ctrl('s')]),
+            Lookup::Command(Command::SaveAs)
+        ));
+        assert_eq!(
+            keymap.bind("ctrl-s", "frobnicate"),
+            Err("Unknown command frobnicate".to_string())
+        );
+        assert!(keymap.bind("hyper-s", "save").is_err());
+    }
+
+    #[test]
+    fn binds_prompt_keys() {
+        let mut keymap = Keymap::new();
+        assert_eq!(
+            keymap.lookup_prompt(Key::Char('\n')),
+            Some(PromptCommand::Accept)
+        );
+        assert_eq!(
+            keymap.lookup_prompt(Key::BackTab),
+            Some(PromptCommand::CompleteBackward)
+        );
+        assert_eq!(keymap.lookup_prompt(Key::Ctrl('g')), None);
+
+        keymap.bind_prompt("ctrl-g", "cancel").unwrap();
+        keymap.bind_prompt("ctrl-q", "none").unwrap();
+        assert_eq!(
+            keymap.lookup_prompt(Key::Ctrl('g')),
+            Some(PromptCommand::Cancel)
+        );
+        assert_eq!(keymap.lookup_prompt(Key::Ctrl('q')), None);
+        // the keys of the editor are left alone
+        assert!(matches!(
+            keymap.lookup(&[Key::Ctrl('q')]),
+            Lookup::Command(Command::Close)
+        ));
+
+        assert_eq!(
+            keymap.bind_prompt("ctrl-k ctrl-c", "cancel"),
+            Err("Prompts don't take sequences of keys".to_string())
+        );
+        assert_eq!(
+            keymap.bind_prompt("ctrl-g", "save"),
+            Err("Unknown prompt command save".to_string())
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use super::input;
+use crate::data::is_word_char;
+use crate::view::View;
+use std::env;
+use std::fs;
+use termion::event::{Event, Key};
+
+/// Text typed in a prompt, edited around a cursor
+#[derive(Debug, Clone, Default)]
+pub struct LineEdit {
+    chars: Vec<char>,
+    cursor: usize,
+    // paths the text can be completed to, along with the one it was completed to
+    completion: Option<(Vec<String>, usize)>,
+    // receiving text pasted in the terminal
+    pasting: bool,
+}
+
+impl LineEdit {
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    // Replaces the text, leaving the cursor at its end
+    pub fn set_text(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    // Inserts text at the cursor, as a single line
+    pub fn insert(&mut self, text: &str) {
+        for c in text.chars().filter(|&c| c != '\n' && c != '\r') {
+            self.chars.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    // Whether text pasted in the terminal is coming, every key of which is to be typed in
+    pub fn is_pasting(&self) -> bool {
+        self.pasting
+    }
+
+    pub fn is_completing(&self) -> bool {
+        self.completion.is_some()
+    }
+
+    // Shows the text on the message line, and the candidates of the completion above it
+    pub fn show(&self, view: &mut View, prompt: &str) {
+        view.prompt_at(prompt, &self.text(), self.cursor);
+        match self.completion {
+            Some((ref candidates, index)) => view.show_list(candidates.clone(), index),
+            None => view.hide_list(),
+        }
+    }
+
+    // Applies an editing key, returning false for the keys left to the prompt
+    pub fn edit(&mut self, event: &Event) -> bool {
+        let handled = if self.pasting {
+            match *event {
+                Event::Unsupported(ref u) if u.as_slice() == input::PASTE_END => {
+                    self.pasting = false
+                }
+                Event::Key(Key::Char(c)) => self.insert(&c.to_string()),
+                _ => {}
+            }
+            true
+        } else {
+            self.edit_key(event)
+        };
+        if handled {
+            self.completion = None;
+        }
+        handled
+    }
+
+    fn edit_key(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::Key(Key::Char(c)) if c != '\n' && c != '\t' => self.insert(&c.to_string()),
+            Event::Key(Key::Backspace) => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Event::Key(Key::Delete) => {
+                if self.cursor < self.chars.len() {
+                    self.chars.remove(self.cursor);
+                }
+            }
+            Event::Key(Key::Left) => self.cursor = self.cursor.saturating_sub(1),
+            Event::Key(Key::Right) => {
+                if self.cursor < self.chars.len() {
+                    self.cursor += 1;
+                }
+            }
+            Event::Key(Key::Home) | Event::Key(Key::Ctrl('a')) => self.cursor = 0,
+            Event::Key(Key::End) | Event::Key(Key::Ctrl('e')) => self.cursor = self.chars.len(),
+            Event::Key(Key::Alt('b')) => self.cursor = self.word_start(),
+            Event::Key(Key::Alt('f')) => self.cursor = self.word_end(),
+            Event::Key(Key::Ctrl('w')) | Event::Key(Key::Alt('\x7f')) => {
+                let start = self.word_start();
+                self.chars.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Event::Key(Key::Alt('d')) => {
+                let end = self.word_end();
+                self.chars.drain(self.cursor..end);
+            }
+            Event::Key(Key::Ctrl('u')) => {
+                self.chars.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Event::Key(Key::Ctrl('k')) => self.chars.truncate(self.cursor),
+            Event::Unsupported(ref u) if u.as_slice() == input::PASTE_START => self.pasting = true,
+            Event::Unsupported(ref u) => match input::parse_modified_key(u) {
+                Some((Key::Left, modifiers)) if modifiers.ctrl => self.cursor = self.word_start(),
+                Some((Key::Right, modifiers)) if modifiers.ctrl => self.cursor = self.word_end(),
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    // Start of the word before the cursor
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !is_word_char(self.chars[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && is_word_char(self.chars[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    // End of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.chars.len() && !is_word_char(self.chars[i]) {
+            i += 1;
+        }
+        while i < self.chars.len() && is_word_char(self.chars[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    // Completes the text as a path, going through the candidates on each call
+    pub fn complete_path(&mut self, forward: bool) {
+        self.complete(forward, path_candidates);
+    }
+
+    // Completes the text with what candidates gives for it, going through them on each call
+    pub fn complete<F>(&mut self, forward: bool, candidates: F)
+    where
+        F: FnOnce(&str) -> Vec<String>,
+    {
+        if let Some((ref candidates, ref mut index)) = self.completion {
+            *index = if forward {
+                (*index + 1) % candidates.len()
+            } else {
+                (*index + candidates.len() - 1) % candidates.len()
+            };
+            let text = candidates[*index].clone();
+            self.set_text(&text);
+            return;
+        }
+        let candidates = candidates(&self.text());
+        match candidates.len() {
+            0 => {}
+            1 => self.set_text(&candidates[0]),
+            _ => {
+                let index = if forward { 0 } else { candidates.len() - 1 };
+                self.set_text(&candidates[index]);
+                self.completion = Some((candidates, index));
+            }
+        }
+    }
+}
+
+// Paths starting with the given one, directories ending with a slash
+pub(super) fn path_candidates(path: &str) -> Vec<String> {
+    let (dir, prefix) = match path.rfind('/') {
+        Some(i) => path.split_at(i + 1),
+        None => ("", path),
+    };
+    // the home directory gets expanded, as there is no shell to do it
+    let dir = match env::var("HOME") {
+        Ok(ref home) if dir.starts_with("~/") => format!("{}{}", home, &dir[1..]),
+        _ => dir.to_string(),
+    };
+    let entries = match fs::read_dir(if dir.is_empty() { "." } else { &dir }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // hidden files only come up when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: Key) -> Event {
+        Event::Key(key)
+    }
+
+    fn line_at(text: &str, cursor: usize) -> LineEdit {
+        let mut line = LineEdit::default();
+        line.set_text(text);
+        line.cursor = cursor;
+        line
+    }
+
+    fn candidates(_: &str) -> Vec<String> {
+        vec!["one".to_string(), "two".to_string(), "three".to_string()]
+    }
+
+    #[test]
+    fn types_around_the_cursor() {
+        let mut line = line_at("ac", 1);
+        assert!(line.edit(&key(Key::Char('b'))));
+        assert_eq!((line.text(), line.cursor), ("abc".to_string(), 2));
+        line.edit(&key(Key::Backspace));
+        line.edit(&key(Key::Delete));
+        assert_eq!((line.text(), line.cursor), ("a".to_string(), 1));
+        line.edit(&key(Key::Backspace));
+        line.edit(&key(Key::Backspace));
+        line.edit(&key(Key::Delete));
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    fn leaves_prompt_keys() {
+        let mut line = line_at("a", 1);
+        assert!(!line.edit(&key(Key::Char('\n'))));
+        assert!(!line.edit(&key(Key::Char('\t'))));
+        assert!(!line.edit(&key(Key::Esc)));
+        assert!(!line.edit(&key(Key::Up)));
+        assert_eq!(line.text(), "a");
+    }
+
+    #[test]
+    fn moves_by_words() {
+        let mut line = line_at("foo bar_baz  qux", 16);
+        line.edit(&key(Key::Alt('b')));
+        assert_eq!(line.cursor, 13);
+        line.edit(&key(Key::Alt('b')));
+        assert_eq!(line.cursor, 4);
+        line.edit(&key(Key::Alt('f')));
+        assert_eq!(line.cursor, 11);
+        line.edit(&key(Key::Ctrl('a')));
+        assert_eq!(line.cursor, 0);
+        line.edit(&key(Key::Left));
+        assert_eq!(line.cursor, 0);
+        line.edit(&key(Key::Ctrl('e')));
+        assert_eq!(line.cursor, 16);
+        line.edit(&key(Key::Right));
+        assert_eq!(line.cursor, 16);
+        line.edit(&Event::Unsupported(b"\x1b[1;5D".to_vec()));
+        assert_eq!(line.cursor, 13);
+    }
+
+    #[test]
+    fn deletes_words_and_ends() {
+        let mut line = line_at("foo bar baz", 7);
+        line.edit(&key(Key::Ctrl('w')));
+        assert_eq!((line.text(), line.cursor), ("foo  baz".to_string(), 4));
+        line.edit(&key(Key::Alt('d')));
+        assert_eq!((line.text(), line.cursor), ("foo ".to_string(), 4));
+
+        let mut line = line_at("foo bar", 3);
+        line.edit(&key(Key::Ctrl('k')));
+        assert_eq!(line.text(), "foo");
+        line.cursor = 1;
+        line.edit(&key(Key::Ctrl('u')));
+        assert_eq!((line.text(), line.cursor), ("oo".to_string(), 0));
+    }
+
+    #[test]
+    fn pastes_on_one_line() {
+        let mut line = line_at("", 0);
+        assert!(line.edit(&Event::Unsupported(input::PASTE_START.to_vec())));
+        for c in "a\nb\t".chars() {
+            assert!(line.edit(&key(Key::Char(c))));
+        }
+        assert!(line.edit(&Event::Unsupported(input::PASTE_END.to_vec())));
+        assert_eq!(line.text(), "ab\t");
+        assert!(!line.edit(&key(Key::Char('\n'))));
+
+        let mut line = line_at("ad", 1);
+        line.insert("b\r\nc");
+        assert_eq!((line.text(), line.cursor), ("abcd".to_string(), 3));
+    }
+
+    #[test]
+    fn completes_through_candidates() {
+        let mut line = line_at("", 0);
+        line.complete(true, candidates);
+        assert_eq!(line.text(), "one");
+        assert!(line.is_completing());
+        line.complete(true, candidates);
+        line.complete(true, candidates);
+        assert_eq!(line.text(), "three");
+        line.complete(true, candidates);
+        assert_eq!(line.text(), "one");
+        line.complete(false, candidates);
+        assert_eq!(line.text(), "three");
+        // editing ends the completion
+        line.edit(&key(Key::Backspace));
+        assert!(!line.is_completing());
+
+        let mut line = line_at("", 0);
+        line.complete(false, candidates);
+        assert_eq!(line.text(), "three");
+    }
+
+    #[test]
+    fn completes_single_candidate() {
+        let mut line = line_at("x", 1);
+        line.complete(true, |_| vec!["xyz".to_string()]);
+        assert_eq!(line.text(), "xyz");
+        assert!(!line.is_completing());
+        line.complete(true, |_| Vec::new());
+        assert_eq!(line.text(), "xyz");
+    }
+
+    #[test]
+    fn completes_paths() {
+        let dir = env::temp_dir().join(format!("smith-line-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("file.rs"), "").unwrap();
+        fs::write(dir.join("fine.rs"), "").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        let dir_name = format!("{}/", dir.to_str().unwrap());
+
+        let names = |prefix: &str| -> Vec<String> {
+            path_candidates(&format!("{}{}", dir_name, prefix))
+                .into_iter()
+                .map(|path| path[dir_name.len()..].to_string())
+                .collect()
+        };
+        assert_eq!(names(""), vec!["file.rs", "fine.rs", "sub/"]);
+        assert_eq!(names("fil"), vec!["file.rs"]);
+        assert_eq!(names("."), vec![".hidden"]);
+        assert!(names("x").is_empty());
+        assert!(path_candidates(&format!("{}nope/", dir_name)).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
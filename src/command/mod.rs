@@ -1,6 +1,32 @@
+mod clipboard;
+mod confirm;
+mod context;
+mod ex;
+mod finder;
+mod goto;
+mod history;
+mod input;
+mod keymap;
+mod line_edit;
+mod picker;
+mod replace;
+mod save;
+mod search;
+mod vi;
+
+use self::clipboard::Clipboard;
+pub use self::context::Context;
+use self::history::History;
+use self::input::Modifiers;
+use self::keymap::{Command, Lookup, PromptCommand};
+use self::line_edit::LineEdit;
+use self::picker::{PickAction, Picker};
+use self::replace::Replace;
+pub use self::vi::Vi;
+use self::vi::{Pending, Visual};
+use crate::config::Overrides;
 use crate::data::*;
 use crate::view::View;
-use clipboard::{ClipboardContext, ClipboardProvider};
 use std::cmp;
 use termion::event::{Event, Key, MouseButton, MouseEvent};
 
@@ -8,110 +34,223 @@ use termion::event::{Event, Key, MouseButton, MouseEvent};
 pub enum State {
     Insert,
     Message,
-    Prompt(String, String, PromptAction),
+    Prompt(String, LineEdit, PromptAction),
     Select(usize),
     Selected,
+    // block selection between an anchor and a head, as lines and visual columns
+    SelectBlock((usize, usize), (usize, usize)),
+    // right after pasting the given number of chars from an entry of the clipboard history
+    Pasted(usize, usize),
+    // receiving text pasted in the terminal
+    Paste(String),
+    Pick(Picker),
+    // waiting for the rest of a sequence of keys bound to a command
+    Chord(Vec<Key>),
+    // modes of modal editing, normal mode waiting for the rest of a command
+    Normal(Pending),
+    ViInsert,
+    Visual(Visual),
+    // signals for the editor, which handles the buffers
     Open(String),
-    Exit,
+    Close,
+    // quit once the changes to all the buffers are saved or discarded, save for those given
+    QuitAll(Vec<usize>),
+    CycleBuffer(isize),
+    ListBuffers,
+    ListRecent,
+    SwitchBuffer(usize),
+    Pane(PaneAction),
+    // settings changed for the buffer
+    Set(Overrides),
+}
+
+#[derive(Debug, Clone)]
+pub enum PaneAction {
+    // side by side rather than one above the other
+    Split(bool),
+    // towards one of the arrow keys
+    Focus(Key),
+    Resize(isize),
+    Close,
 }
 
 #[derive(Debug, Clone)]
 pub enum PromptAction {
     Save,
-    ConfirmExit,
-    Open,
-    ConfirmOpen(String),
+    SaveAs,
+    // save under another name, removing the old file
+    Rename,
+    ConfirmClose,
+    // changes to a buffer before quitting, those of the others given being discarded
+    ConfirmQuit(usize, Vec<usize>),
+    // save under a name, then go on to another state
+    SaveThen(Box<State>),
+    // incremental search started with the cursor at the given position
+    Search(Search, usize),
+    // pattern to replace, in the selection if any
+    Replace(Option<Selection>),
+    ReplaceWith(String, Option<Selection>),
+    ConfirmReplace(Replace),
+    Goto,
+    // command line, such as "w file" or "%s/a/b/g"
+    Ex,
 }
 
-const SCROLL_FACTOR: usize = 2;
+impl PromptAction {
+    // Name under which the entries of the prompt are remembered, if they are worth it
+    fn history_name(&self) -> Option<&'static str> {
+        match *self {
+            PromptAction::Save
+            | PromptAction::SaveAs
+            | PromptAction::Rename
+            | PromptAction::SaveThen(_) => Some("path"),
+            PromptAction::Replace(_) => Some("pattern"),
+            PromptAction::ReplaceWith(..) => Some("replacement"),
+            PromptAction::Goto => Some("goto"),
+            PromptAction::Ex => Some("command"),
+            _ => None,
+        }
+    }
+
+    fn completes_paths(&self) -> bool {
+        matches!(
+            *self,
+            PromptAction::Save
+                | PromptAction::SaveAs
+                | PromptAction::Rename
+                | PromptAction::SaveThen(_)
+        )
+    }
+}
 
 impl State {
     // Handles a Termion event, consuming the current state and returning the new state
-    pub fn handle<T>(self, content: &mut T, view: &mut View, event: Event) -> Self
+    pub fn handle<T>(
+        self,
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+    ) -> Self
     where
         T: Editable + Saveable + Undoable + Selectable + Modifiable,
     {
-        match self {
-            State::Prompt(prompt, message, action) => {
-                State::handle_prompt(content, view, event, prompt, message, action)
+        let searching = matches!(self, State::Prompt(_, _, PromptAction::Search(..)));
+        let state = match self {
+            // in normal or insert mode, depending on the one the message came up in
+            State::Message if ctx.vi.is_some() => {
+                view.quiet();
+                let state = State::Insert.in_mode(content, ctx);
+                return state.handle(content, view, ctx, event);
+            }
+            State::Prompt(_, line, PromptAction::Search(search, origin)) => {
+                State::handle_search(content, view, ctx, event, line, search, origin)
+            }
+            State::Prompt(_, _, PromptAction::ConfirmReplace(replace)) => {
+                State::handle_replace(content, view, event, replace)
+            }
+            State::Prompt(prompt, _, action @ PromptAction::ConfirmClose)
+            | State::Prompt(prompt, _, action @ PromptAction::ConfirmQuit(..)) => {
+                State::handle_confirm(content, view, event, prompt, action)
+            }
+            State::Prompt(prompt, line, action) => {
+                State::handle_prompt(content, view, ctx, event, prompt, line, action)
             }
             State::Select(origin) => State::handle_select(content, view, event, origin),
-            State::Insert => State::handle_insert(content, view, event),
-            State::Message => State::handle_message(content, view, event),
-            State::Selected => State::handle_selected(content, view, event),
-            State::Open(_) | State::Exit => panic!("Can't handle exit state"),
+            State::Insert => State::handle_insert(content, view, ctx, event),
+            State::Message => State::handle_message(content, view, ctx, event),
+            State::Selected => State::handle_selected(content, view, ctx, event),
+            State::Paste(text) => State::handle_paste(content, view, event, text),
+            State::Pasted(len, index) => {
+                State::handle_pasted(content, view, ctx, event, len, index)
+            }
+            State::SelectBlock(anchor, head) => {
+                State::handle_select_block(content, view, ctx, event, anchor, head)
+            }
+            State::Pick(picker) => State::handle_pick(content, view, ctx, event, picker),
+            State::Chord(keys) => State::handle_chord(content, view, ctx, event, keys),
+            State::Normal(pending) => State::handle_normal(content, view, ctx, event, pending),
+            State::ViInsert => State::handle_vi_insert(content, view, ctx, event),
+            State::Visual(visual) => State::handle_visual(content, view, ctx, event, visual),
+            State::Open(_)
+            | State::Close
+            | State::QuitAll(_)
+            | State::CycleBuffer(_)
+            | State::ListBuffers
+            | State::ListRecent
+            | State::SwitchBuffer(_)
+            | State::Pane(_)
+            | State::Set(_) => panic!("Can't handle editor signal"),
+        };
+        // a search leaves the cursor on the match found in normal mode, rather than selecting it
+        match state {
+            State::Selected if searching && ctx.vi.is_some() => {
+                vi::leave_match(content);
+                State::Insert.in_mode(content, ctx)
+            }
+            state => state.in_mode(content, ctx),
         }
     }
 
-    fn handle_message<T>(content: &mut T, view: &mut View, event: Event) -> Self
+    // Lets the user pick one of the open buffers
+    pub fn pick_buffer(view: &mut View, names: Vec<String>, current: usize) -> Self {
+        Picker::new(names, current, PickAction::Buffer).show(view)
+    }
+
+    // Lets the user pick one of the files opened before
+    pub fn pick_recent(view: &mut View, paths: Vec<String>) -> Self {
+        Picker::new(paths, 0, PickAction::Recent).show(view)
+    }
+
+    fn handle_message<T>(content: &mut T, view: &mut View, ctx: &mut Context, event: Event) -> Self
     where
-        T: Editable + Named + Undoable + Modifiable + Saveable,
+        T: Editable + Named + Undoable + Modifiable + Saveable + Selectable,
     {
         view.quiet();
-        Self::handle_insert(content, view, event)
+        Self::handle_insert(content, view, ctx, event)
     }
 
-    fn handle_insert<T>(content: &mut T, view: &mut View, event: Event) -> Self
+    fn handle_insert<T>(content: &mut T, view: &mut View, ctx: &mut Context, event: Event) -> Self
     where
-        T: Editable + Named + Undoable + Modifiable + Saveable,
+        T: Editable + Named + Undoable + Modifiable + Saveable + Selectable,
     {
-        match event {
-            Event::Key(Key::Ctrl('q')) | Event::Key(Key::Esc) => {
-                if content.was_modified() {
-                    let prompt = "Changes not saved do you really want to exit (y/N): ".to_string();
-                    let message = "".to_string();
-                    view.prompt(&prompt, &message);
-                    return State::Prompt(prompt, message, PromptAction::ConfirmExit);
-                } else {
-                    return State::Exit;
-                }
+        if let Event::Key(key) = event {
+            match ctx.keymap.lookup(&[key]) {
+                Lookup::Command(command) => return State::run_command(command, content, view, ctx),
+                Lookup::Prefix => return State::start_chord(view, vec![key]),
+                Lookup::Unbound => {}
             }
-            Event::Key(Key::Ctrl('s')) => {
-                if content.name().is_empty() {
-                    let prompt = "Save to: ".to_string();
-                    view.prompt(&prompt, "");
-                    return State::Prompt(prompt, "".to_string(), PromptAction::Save);
-                } else {
-                    let msg = match content.save() {
-                        Err(e) => e.to_string(),
-                        Ok(_) => format!("Saved file {}", content.name()),
-                    };
-                    view.message(&msg);
-                    return State::Message;
-                }
+        }
+        match event {
+            Event::Key(Key::Esc) if !content.cursors().is_empty() => {
+                content.reset_cursors();
             }
-            Event::Key(Key::Ctrl('o')) => {
-                let prompt = "Open file: ".to_string();
-                let message = "".to_string();
-                view.prompt(&prompt, &message);
-                return State::Prompt(prompt, message, PromptAction::Open);
+            Event::Key(Key::Esc) => {
+                return State::run_command(Command::Close, content, view, ctx);
             }
             Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) => {
                 let (line, col) = view.translate_coordinates(content, x, y);
+                content.reset_cursors();
                 content.move_at(line, col);
-                return State::Select(content.pos());
+                return match view.click(x, y) {
+                    2 => select_word(content),
+                    3 => select_line(content),
+                    _ => State::Select(content.pos()),
+                };
             }
             Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, _, _)) => {
-                view.scroll_view(SCROLL_FACTOR as isize, content);
+                view.scroll_view(view.settings().scroll_speed as isize, content);
             }
             Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, _, _)) => {
-                view.scroll_view(-(SCROLL_FACTOR as isize), content);
-            }
-            Event::Key(Key::Ctrl('z')) => {
-                content.undo();
-            }
-            Event::Key(Key::Ctrl('y')) => {
-                content.redo();
+                view.scroll_view(-(view.settings().scroll_speed as isize), content);
             }
-            Event::Key(Key::Ctrl('v')) => {
-                let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-                for c in ctx
-                    .get_contents()
-                    .unwrap_or_else(|_| "".to_string())
-                    .chars()
-                {
-                    content.insert(c);
-                }
+            Event::Mouse(MouseEvent::Press(MouseButton::Middle, x, y)) => {
+                let (line, col) = view.translate_coordinates(content, x, y);
+                content.reset_cursors();
+                content.move_at(line, col);
+                let len = paste(content, &mut ctx.clipboard);
+                view.adjust_view(content.line());
+                return State::Pasted(len, 0);
             }
             Event::Key(Key::Up) => {
                 content.step(Movement::Up);
@@ -130,11 +269,11 @@ impl State {
                 view.adjust_view(content.line());
             }
             Event::Key(Key::PageUp) => {
-                content.step(Movement::PageUp(view.lines_height() as usize));
+                content.step(Movement::PageUp(view.lines_height()));
                 view.center_view(content.line());
             }
             Event::Key(Key::PageDown) => {
-                content.step(Movement::PageDown(view.lines_height() as usize));
+                content.step(Movement::PageDown(view.lines_height()));
                 view.center_view(content.line());
             }
             Event::Key(Key::Home) => {
@@ -151,13 +290,74 @@ impl State {
                 content.delete_forward();
                 view.adjust_view(content.line());
             }
+            Event::Key(Key::Char('\n')) if view.settings().auto_indent => {
+                new_line(content);
+                view.adjust_view(content.line());
+            }
+            Event::Key(Key::Char('\t')) if view.settings().soft_tabs => {
+                let col = view.visual_col(content, content.line(), content.col());
+                let width = view.settings().tab_width;
+                for _ in 0..width - col % width {
+                    content.insert(' ');
+                }
+                view.adjust_view(content.line());
+            }
             Event::Key(Key::Char(c)) => {
                 content.insert(c);
                 view.adjust_view(content.line());
             }
-            Event::Unsupported(u) => {
-                view.message(&format!("Unsupported escape sequence {:?}", u));
+            Event::Unsupported(ref u) if search::is_shift_f3(u) => {
+                return search::repeat_search(content, view, ctx, false);
             }
+            Event::Unsupported(ref u) if u.as_slice() == input::PASTE_START => {
+                return State::Paste(String::new());
+            }
+            Event::Unsupported(u) => match input::parse_modified_key(&u) {
+                Some((key, modifiers)) if modifiers.shift && modifiers.alt => {
+                    return start_block(content, view, key);
+                }
+                Some((key, modifiers)) if modifiers.shift => {
+                    return extend_sel(content, view, key, modifiers);
+                }
+                Some((Key::PageDown, modifiers)) if modifiers.ctrl => {
+                    return State::CycleBuffer(1);
+                }
+                Some((Key::PageUp, modifiers)) if modifiers.ctrl => {
+                    return State::CycleBuffer(-1);
+                }
+                Some((key, modifiers)) if modifiers.alt && !modifiers.ctrl => {
+                    return State::Pane(PaneAction::Focus(key));
+                }
+                Some((Key::Up, modifiers)) if modifiers.ctrl && modifiers.alt => {
+                    add_cursor_at_line(content, view, content.line().checked_sub(1));
+                }
+                Some((Key::Down, modifiers)) if modifiers.ctrl && modifiers.alt => {
+                    add_cursor_at_line(content, view, Some(content.line() + 1));
+                }
+                Some((key, modifiers)) => {
+                    if let Some(mov) = movement(view, key, modifiers) {
+                        content.step(mov);
+                        view.adjust_view(content.line());
+                    }
+                }
+                None => match input::parse_modified_mouse(&u) {
+                    Some((MouseEvent::Press(MouseButton::Left, x, y), modifiers))
+                        if modifiers.shift =>
+                    {
+                        return extend_sel_to(content, view, x, y);
+                    }
+                    Some((MouseEvent::Press(MouseButton::Left, x, y), modifiers))
+                        if modifiers.alt =>
+                    {
+                        let anchor = view.translate_visual_coordinates(content, x, y);
+                        return select_block(content, view, anchor, anchor);
+                    }
+                    Some(_) => {}
+                    None => {
+                        view.message(&format!("Unsupported escape sequence {:?}", u));
+                    }
+                },
+            },
             _ => {}
         }
         State::Insert
@@ -166,153 +366,309 @@ impl State {
     fn handle_prompt<T>(
         content: &mut T,
         view: &mut View,
+        ctx: &mut Context,
         event: Event,
         prompt: String,
-        mut message: String,
+        mut line: LineEdit,
         action: PromptAction,
     ) -> Self
     where
-        T: Editable + Saveable + Modifiable,
+        T: Editable + Saveable + Modifiable + Selectable + Undoable,
     {
-        match event {
-            Event::Key(Key::Char('\n')) => match action {
-                PromptAction::Save => {
-                    let msg: String;
-                    let old_name = content.name().clone();
-                    content.set_name(message.clone());
-                    msg = match content.save() {
-                        Err(e) => {
-                            content.set_name(old_name);
-                            e.to_string()
-                        }
-                        Ok(_) => format!("Saved file {}", message),
-                    };
-                    view.message(&msg);
-                    State::Message
+        let command = match event {
+            Event::Key(key) if !line.is_pasting() => ctx.keymap.lookup_prompt(key),
+            _ => None,
+        };
+        let command = match command {
+            Some(command) => command,
+            None => {
+                if line.edit(&event) {
+                    line.show(view, &prompt);
                 }
-                PromptAction::ConfirmExit => {
-                    if message.to_lowercase() == "y" {
-                        State::Exit
-                    } else {
-                        view.message("");
-                        State::Message
-                    }
+                return State::Prompt(prompt, line, action);
+            }
+        };
+        match command {
+            PromptCommand::Accept => {
+                view.hide_list();
+                let message = line.text();
+                if let Some(name) = action.history_name() {
+                    ctx.prompt_history
+                        .entry(name)
+                        .or_insert_with(History::new)
+                        .push(message.clone());
                 }
-                PromptAction::Open => {
-                    let filename = message;
-                    if content.was_modified() {
-                        let prompt =
-                            "Changes not saved do you really want to open a new file (y/N): "
-                                .to_string();
-                        let message = "".to_string();
-                        view.prompt(&prompt, &message);
-                        State::Prompt(prompt, message, PromptAction::ConfirmOpen(filename))
-                    } else {
-                        State::Open(filename)
+                match action {
+                    PromptAction::Save | PromptAction::SaveAs => {
+                        save::save_as(content, view, message)
+                    }
+                    PromptAction::Rename => save::rename(content, view, message),
+                    PromptAction::SaveThen(next) => save::save_then(content, view, message, *next),
+                    PromptAction::Replace(scope) => replace::ask_replacement(view, message, scope),
+                    PromptAction::ReplaceWith(pattern, scope) => {
+                        replace::confirm_replace(content, view, &pattern, &message, scope)
+                    }
+                    PromptAction::Goto => goto::goto(content, view, &message),
+                    PromptAction::Ex => ex::run(content, view, &message),
+                    PromptAction::Search(..)
+                    | PromptAction::ConfirmReplace(_)
+                    | PromptAction::ConfirmClose
+                    | PromptAction::ConfirmQuit(..) => {
+                        panic!("Prompt is handled on its own")
                     }
                 }
-                PromptAction::ConfirmOpen(filename) => {
-                    if message.to_lowercase() == "y" {
-                        State::Open(filename)
+            }
+            PromptCommand::Complete | PromptCommand::CompleteBackward => {
+                let forward = command == PromptCommand::Complete;
+                if action.completes_paths() {
+                    line.complete_path(forward);
+                } else if matches!(action, PromptAction::Ex) {
+                    line.complete(forward, ex::candidates);
+                }
+                line.show(view, &prompt);
+                State::Prompt(prompt, line, action)
+            }
+            PromptCommand::Paste => {
+                line.insert(&ctx.clipboard.paste());
+                line.show(view, &prompt);
+                State::Prompt(prompt, line, action)
+            }
+            PromptCommand::HistoryOlder | PromptCommand::HistoryNewer => {
+                let history = action
+                    .history_name()
+                    .and_then(|name| ctx.prompt_history.get(name));
+                if let Some(history) = history {
+                    let entry = if command == PromptCommand::HistoryOlder {
+                        history.older(&line.text()).cloned()
                     } else {
-                        view.message("");
-                        State::Message
+                        history.newer(&line.text())
+                    };
+                    if let Some(entry) = entry {
+                        line.set_text(&entry);
                     }
                 }
-            },
-            Event::Key(Key::Char('\t')) => State::Prompt(prompt, message, action), // TODO: autocompletion
-            Event::Key(Key::Char(c)) => {
-                message.push(c);
-                view.prompt(&prompt, &message);
-                State::Prompt(prompt, message, action)
+                line.show(view, &prompt);
+                State::Prompt(prompt, line, action)
             }
-            Event::Key(Key::Backspace) | Event::Key(Key::Delete) => {
-                message.pop();
-                view.prompt(&prompt, &message);
-                State::Prompt(prompt, message, action)
-            }
-            Event::Key(Key::Ctrl('q')) => State::Exit,
-            Event::Key(Key::Esc) => {
+            PromptCommand::Cancel => {
+                view.hide_list();
                 view.quiet();
                 State::Insert
             }
-            _ => State::Prompt(prompt, message, action),
         }
     }
 
-    fn handle_selected<T>(content: &mut T, view: &mut View, event: Event) -> Self
+    fn handle_selected<T>(content: &mut T, view: &mut View, ctx: &mut Context, event: Event) -> Self
     where
         T: Selectable + Editable + Named + Undoable + Modifiable + Saveable,
     {
+        if let Event::Key(key) = event {
+            match ctx.keymap.lookup(&[key]) {
+                Lookup::Command(command) => return State::run_command(command, content, view, ctx),
+                Lookup::Prefix => return State::start_chord(view, vec![key]),
+                Lookup::Unbound => {}
+            }
+        }
         match event {
-            Event::Key(Key::Ctrl('c')) => {
-                let (beg, end) = content.sel().unwrap();
-
-                let selection: String = content.iter().skip(beg).take(end - beg + 1).collect();
-                let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-                ctx.set_contents(selection).unwrap();
-
+            Event::Key(Key::Backspace) | Event::Key(Key::Delete) => {
+                content.delete_sel();
+                view.adjust_view(content.line());
                 content.reset_sel();
                 State::Insert
             }
-            Event::Key(Key::Ctrl('x')) => {
-                let (beg, end) = content.sel().unwrap();
-
-                let selection: String = content.iter().skip(beg).take(end - beg + 1).collect();
-                let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-                ctx.set_contents(selection).unwrap();
-
-                delete_sel(content);
+            Event::Unsupported(ref u) if search::is_shift_f3(u) => {
+                search::repeat_search(content, view, ctx, false)
+            }
+            Event::Unsupported(ref u) if u.as_slice() == input::PASTE_START => {
+                // pasted text replaces the selection
+                content.delete_sel();
                 view.adjust_view(content.line());
-
                 content.reset_sel();
-                State::Insert
+                State::Paste(String::new())
             }
-            Event::Key(Key::Backspace) | Event::Key(Key::Delete) => {
-                delete_sel(content);
+            Event::Key(Key::Char(_)) => {
+                content.begin_group();
+                content.delete_sel();
+                view.adjust_view(content.line());
+                content.reset_sel();
+                let state = Self::handle_insert(content, view, ctx, event);
+                content.end_group();
+                state
+            }
+            Event::Key(Key::Left) => {
+                let sel = content.sel().unwrap();
+                content.move_to(sel.beg());
                 view.adjust_view(content.line());
                 content.reset_sel();
                 State::Insert
             }
-            Event::Key(Key::Char(_)) => {
-                delete_sel(content);
+            Event::Key(Key::Right) => {
+                let sel = content.sel().unwrap();
+                content.move_to(sel.end());
                 view.adjust_view(content.line());
                 content.reset_sel();
-                Self::handle_insert(content, view, event)
+                State::Insert
+            }
+            Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, _, _))
+            | Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, _, _)) => {
+                Self::handle_insert(content, view, ctx, event);
+                State::Selected
             }
+            // what is left of a multiple click
+            Event::Mouse(MouseEvent::Hold(_, _)) | Event::Mouse(MouseEvent::Release(_, _)) => {
+                State::Selected
+            }
+            Event::Unsupported(ref u) => match input::parse_modified_key(u) {
+                Some((key, modifiers)) if modifiers.shift && modifiers.alt => {
+                    start_block(content, view, key)
+                }
+                Some((key, modifiers)) if modifiers.shift => {
+                    extend_sel(content, view, key, modifiers)
+                }
+                _ => match input::parse_modified_mouse(u) {
+                    Some((MouseEvent::Press(MouseButton::Left, x, y), modifiers))
+                        if modifiers.shift =>
+                    {
+                        extend_sel_to(content, view, x, y)
+                    }
+                    _ => {
+                        content.reset_sel();
+                        Self::handle_insert(content, view, ctx, event)
+                    }
+                },
+            },
             _ => {
                 content.reset_sel();
-                Self::handle_insert(content, view, event)
+                Self::handle_insert(content, view, ctx, event)
             }
         }
     }
 
+    fn handle_paste<T>(content: &mut T, view: &mut View, event: Event, mut text: String) -> Self
+    where
+        T: Editable + Undoable,
+    {
+        match event {
+            Event::Unsupported(ref u) if u.as_slice() == input::PASTE_END => {
+                // the whole text is inserted as is, and undone at once
+                content.begin_group();
+                for c in text.chars() {
+                    content.insert(c);
+                }
+                content.end_group();
+                view.adjust_view(content.line());
+                State::Insert
+            }
+            Event::Key(Key::Char(c)) => {
+                text.push(c);
+                State::Paste(text)
+            }
+            _ => State::Paste(text),
+        }
+    }
+
+    fn handle_pasted<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        len: usize,
+        index: usize,
+    ) -> Self
+    where
+        T: Selectable + Editable + Named + Undoable + Modifiable + Saveable,
+    {
+        match event {
+            Event::Key(Key::Alt('y')) if ctx.clipboard.history_len() > 1 => {
+                // replace what was just pasted by the previous entry of the history
+                let index = (index + 1) % ctx.clipboard.history_len();
+                let text = ctx.clipboard.history(index).unwrap().clone();
+                content.begin_group();
+                for _ in 0..len {
+                    content.delete();
+                }
+                for c in text.chars() {
+                    content.insert(c);
+                }
+                content.end_group();
+                view.adjust_view(content.line());
+                view.message(&format!(
+                    "Clipboard history {}/{}",
+                    index + 1,
+                    ctx.clipboard.history_len()
+                ));
+                State::Pasted(text.chars().count(), index)
+            }
+            _ => {
+                view.quiet();
+                Self::handle_insert(content, view, ctx, event)
+            }
+        }
+    }
+
+    fn handle_select_block<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        anchor: (usize, usize),
+        head: (usize, usize),
+    ) -> Self
+    where
+        T: Selectable + Editable + Named + Undoable + Modifiable + Saveable,
+    {
+        let mouse = match event {
+            Event::Mouse(mouse) => Some(mouse),
+            Event::Unsupported(ref u) => input::parse_modified_mouse(u).map(|(mouse, _)| mouse),
+            _ => None,
+        };
+        match mouse {
+            Some(MouseEvent::Hold(x, y)) => {
+                auto_scroll(content, view, y);
+                let head = view.translate_visual_coordinates(content, x, y);
+                return select_block(content, view, anchor, head);
+            }
+            Some(MouseEvent::Release(_, _)) => return State::SelectBlock(anchor, head),
+            _ => {}
+        }
+        if let Event::Unsupported(ref u) = event {
+            if let Some((key, modifiers)) = input::parse_modified_key(u) {
+                if modifiers.shift && modifiers.alt {
+                    let head = move_block_head(content, head, key);
+                    return select_block(content, view, anchor, head);
+                }
+            }
+        }
+
+        // the block is made of one selection per line, or of column cursors if it is empty
+        if content.sel().is_some_and(|sel| !sel.is_empty()) {
+            Self::handle_selected(content, view, ctx, event)
+        } else {
+            content.reset_sel();
+            Self::handle_insert(content, view, ctx, event)
+        }
+    }
+
     fn handle_select<T>(content: &mut T, view: &mut View, event: Event, origin: usize) -> Self
     where
         T: Editable + Selectable,
     {
         match event {
             Event::Mouse(MouseEvent::Hold(x, y)) => {
+                auto_scroll(content, view, y);
                 let (line, col) = view.translate_coordinates(content, x, y);
                 content.move_at(line, col);
-                let sel = (
-                    cmp::min(origin, content.pos()),
-                    cmp::max(origin, content.pos()),
-                );
-                content.set_sel(sel);
+                content.set_sel(Selection::new(origin, content.pos()));
                 State::Select(origin)
             }
             Event::Mouse(MouseEvent::Release(x, y)) => {
                 let (line, col) = view.translate_coordinates(content, x, y);
                 content.move_at(line, col);
                 if origin != content.pos() {
-                    let sel = (
-                        cmp::min(origin, content.pos()),
-                        cmp::max(origin, content.pos()),
-                    );
-                    content.set_sel(sel);
+                    content.set_sel(Selection::new(origin, content.pos()));
                     State::Selected
                 } else {
+                    content.reset_sel();
                     State::Insert
                 }
             }
@@ -321,15 +677,374 @@ impl State {
     }
 }
 
-fn delete_sel<T>(content: &mut T)
+// Where the mouse was pressed, if it was
+pub fn pressed_at(event: &Event) -> Option<(u16, u16)> {
+    match *event {
+        Event::Mouse(MouseEvent::Press(_, x, y)) => Some((x, y)),
+        Event::Unsupported(ref u) => match input::parse_modified_mouse(u) {
+            Some((MouseEvent::Press(_, x, y), _)) => Some((x, y)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Pastes at every cursor and returns the number of chars pasted
+fn paste<T>(content: &mut T, clipboard: &mut Clipboard) -> usize
+where
+    T: Editable + Undoable,
+{
+    let text = clipboard.paste();
+    content.begin_group();
+    for c in text.chars() {
+        content.insert(c);
+    }
+    content.end_group();
+    text.chars().count()
+}
+
+// Starts a new line indented like the current one, as far as the cursor
+fn new_line<T>(content: &mut T)
+where
+    T: Editable + Undoable,
+{
+    let indent: Vec<char> = content
+        .iter_line(content.line())
+        .take(content.col())
+        .take_while(|&c| c == ' ' || c == '\t')
+        .collect();
+    content.begin_group();
+    content.insert('\n');
+    for c in indent {
+        content.insert(c);
+    }
+    content.end_group();
+}
+
+// Scrolls when dragging the mouse past the top or the bottom of the text
+fn auto_scroll<T>(content: &T, view: &mut View, y: u16)
+where
+    T: Editable,
+{
+    let row = view.row_of(y);
+    if row <= 0 {
+        view.scroll_view(-1, content);
+    } else if row as usize >= view.lines_height() {
+        view.scroll_view(1, content);
+    }
+}
+
+// Extends the selection, or starts one from the cursor, up to the clicked position
+fn extend_sel_to<T>(content: &mut T, view: &mut View, x: u16, y: u16) -> State
+where
+    T: Selectable + Editable,
+{
+    let anchor = content.sel().map_or(content.pos(), |sel| sel.anchor);
+    let (line, col) = view.translate_coordinates(content, x, y);
+    content.reset_cursors();
+    content.move_at(line, col);
+    let sel = Selection::new(anchor, content.pos());
+    if sel.is_empty() {
+        content.reset_sel();
+        State::Insert
+    } else {
+        content.set_sel(sel);
+        State::Selected
+    }
+}
+
+fn select_word<T>(content: &mut T) -> State
+where
+    T: Selectable + Editable,
+{
+    match word_under_cursor(content) {
+        Some(word) => {
+            content.move_to(word.head);
+            content.set_sel(word);
+            State::Selected
+        }
+        None => State::Insert,
+    }
+}
+
+// Selects the line of the cursor along with its line break
+fn select_line<T>(content: &mut T) -> State
+where
+    T: Selectable + Editable,
+{
+    let line = content.line();
+    let beg = content.line_index_to_char_index(line);
+    let end = cmp::min(
+        content.line_index_to_char_index(line + 1),
+        content.len() - 1,
+    );
+    let sel = Selection::new(beg, end);
+    if sel.is_empty() {
+        return State::Insert;
+    }
+    content.move_to(sel.head);
+    content.set_sel(sel);
+    State::Selected
+}
+
+// Selects the whole text, leaving the cursor at its end
+fn select_all<T>(content: &mut T) -> State
+where
+    T: Selectable + Editable,
+{
+    if content.len() < 2 {
+        return State::Insert;
+    }
+    content.move_to(content.len() - 1);
+    content.set_sel(Selection::new(0, content.len() - 1));
+    State::Selected
+}
+
+// Moves every cursor, extending their selections
+fn extend_sel<T>(content: &mut T, view: &mut View, key: Key, modifiers: Modifiers) -> State
 where
     T: Selectable + Editable,
 {
-    let (beg, end) = content.sel().unwrap();
-    assert!(beg < end);
-    let end = cmp::min(end + 1, content.len() - 1);
-    content.move_to(end);
-    for _ in beg..end {
-        content.delete();
+    if content.sel().is_none() {
+        let pos = content.pos();
+        content.set_sel(Selection::new(pos, pos));
+    }
+    if let Some(mov) = movement(view, key, modifiers) {
+        content.step(mov);
+        view.adjust_view(content.line());
+    }
+    let selected = content
+        .cursors()
+        .iter()
+        .chain(content.sel().iter())
+        .any(|sel| !sel.is_empty());
+    if selected {
+        State::Selected
+    } else {
+        content.reset_sel();
+        State::Insert
+    }
+}
+
+// Starts a block selection at the cursor
+fn start_block<T>(content: &mut T, view: &mut View, key: Key) -> State
+where
+    T: Selectable + Editable,
+{
+    let line = content.line();
+    let anchor = (line, view.visual_col(content, line, content.col()));
+    let head = move_block_head(content, anchor, key);
+    select_block(content, view, anchor, head)
+}
+
+fn move_block_head<T>(content: &T, (line, col): (usize, usize), key: Key) -> (usize, usize)
+where
+    T: Editable,
+{
+    match key {
+        Key::Up => (line.saturating_sub(1), col),
+        Key::Down => (cmp::min(line + 1, content.line_count() - 1), col),
+        Key::Left => (line, col.saturating_sub(1)),
+        Key::Right => (line, col + 1),
+        _ => (line, col),
+    }
+}
+
+// Selects the rectangle of visual columns between anchor and head, with one selection per line
+fn select_block<T>(
+    content: &mut T,
+    view: &mut View,
+    anchor: (usize, usize),
+    head: (usize, usize),
+) -> State
+where
+    T: Selectable + Editable,
+{
+    let left = cmp::min(anchor.1, head.1);
+    let right = cmp::max(anchor.1, head.1);
+    let line_sel = |line: usize| {
+        let start = content.line_index_to_char_index(line);
+        let beg = view.col_at(content, line, left);
+        let end = if right > left {
+            view.col_at(content, line, right - 1) + 1
+        } else {
+            beg
+        };
+        let end = cmp::max(beg, cmp::min(end, view.col_at(content, line, usize::MAX)));
+        if head.1 >= anchor.1 {
+            Selection::new(start + beg, start + end)
+        } else {
+            Selection::new(start + end, start + beg)
+        }
+    };
+    let others: Vec<Selection> = (cmp::min(anchor.0, head.0)..=cmp::max(anchor.0, head.0))
+        .filter(|&line| line != head.0)
+        .map(line_sel)
+        .collect();
+    let main = line_sel(head.0);
+
+    content.reset_sel();
+    content.reset_cursors();
+    content.move_to(main.head);
+    for other in others {
+        content.add_cursor(other);
+    }
+    content.set_sel(main);
+    view.adjust_view(content.line());
+    State::SelectBlock(anchor, head)
+}
+
+// Adds a cursor on another line, at the same column, and makes it the main one
+fn add_cursor_at_line<T>(content: &mut T, view: &mut View, line: Option<usize>)
+where
+    T: Selectable + Editable,
+{
+    let line = match line {
+        Some(line) if line < content.line_count() => line,
+        _ => return,
+    };
+    let pos = content.pos();
+    let col = content.col();
+    content.move_at(line, col);
+    content.add_cursor(Selection::new(pos, pos));
+    view.adjust_view(content.line());
+}
+
+// Selects the word under the cursor, or if a selection exists, adds a cursor selecting its next
+// occurrence
+fn add_next_occurrence<T>(content: &mut T, view: &mut View) -> State
+where
+    T: Selectable + Editable,
+{
+    let sel = match *content.sel() {
+        Some(sel) if !sel.is_empty() => sel,
+        _ => return select_word(content),
+    };
+
+    let needle: Vec<char> = content
+        .iter()
+        .skip(sel.beg())
+        .take(sel.end() - sel.beg())
+        .collect();
+    let taken =
+        |pos: usize| pos == sel.beg() || content.cursors().iter().any(|other| other.beg() == pos);
+    let mut from = sel.end();
+    let found = loop {
+        match find_next(content, &needle, from) {
+            Some(pos) if pos == sel.beg() => break None,
+            Some(pos) if taken(pos) => from = pos + 1,
+            found => break found,
+        }
+    };
+
+    match found {
+        Some(pos) => {
+            content.move_to(pos + needle.len());
+            content.add_cursor(sel);
+            content.set_sel(Selection::new(pos, pos + needle.len()));
+            view.adjust_view(content.line());
+        }
+        None => view.message("No more occurrences"),
+    }
+    State::Selected
+}
+
+// Turns the main selection into one selection per line
+fn split_sel_lines<T>(content: &mut T, view: &mut View) -> State
+where
+    T: Selectable + Editable,
+{
+    let sel = content.sel().unwrap();
+    let mut lines = Vec::new();
+    let mut beg = sel.beg();
+    for (i, c) in content
+        .iter()
+        .enumerate()
+        .skip(sel.beg())
+        .take(sel.end() - sel.beg())
+    {
+        if c == '\n' {
+            lines.push(Selection::new(beg, i));
+            beg = i + 1;
+        }
+    }
+    if beg < sel.end() {
+        lines.push(Selection::new(beg, sel.end()));
+    }
+
+    if let Some(last) = lines.pop() {
+        content.move_to(last.head);
+        for line in lines {
+            content.add_cursor(line);
+        }
+        content.set_sel(last);
+        view.adjust_view(content.line());
+    }
+    State::Selected
+}
+
+// Finds the first occurrence of needle at or after from, wrapping around the end of the text,
+// leaving out the final line break the cursor can't go past
+fn find_next<T>(content: &T, needle: &[char], from: usize) -> Option<usize>
+where
+    T: Editable,
+{
+    let text: Vec<char> = content.iter().collect();
+    if needle.is_empty() || needle.len() >= text.len() {
+        return None;
+    }
+    let last = text.len() - 1 - needle.len();
+    (from..=last)
+        .chain(0..cmp::min(from, last + 1))
+        .find(|&i| text[i..i + needle.len()] == *needle)
+}
+
+fn movement(view: &View, key: Key, modifiers: Modifiers) -> Option<Movement> {
+    Some(match key {
+        Key::Up => Movement::Up,
+        Key::Down => Movement::Down,
+        Key::Left if modifiers.ctrl => Movement::WordLeft,
+        Key::Left => Movement::Left,
+        Key::Right if modifiers.ctrl => Movement::WordRight,
+        Key::Right => Movement::Right,
+        Key::Home => Movement::LineStart,
+        Key::End => Movement::LineEnd,
+        Key::PageUp => Movement::PageUp(view.lines_height()),
+        Key::PageDown => Movement::PageDown(view.lines_height()),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Text {
+        let mut text = Text::empty();
+        for c in s.chars() {
+            text.insert(c);
+        }
+        text
+    }
+
+    #[test]
+    fn find_next_wraps_around() {
+        let content = text("ab\nab\nab");
+        let needle: Vec<char> = "ab".chars().collect();
+        assert_eq!(find_next(&content, &needle, 1), Some(3));
+        assert_eq!(find_next(&content, &needle, 7), Some(0));
+        assert_eq!(find_next(&content, &['x'], 0), None);
+    }
+
+    #[test]
+    fn find_next_skips_final_line_break() {
+        // the second "a\n" ends on the line break the cursor can't reach
+        let content = text("a\na");
+        let needle: Vec<char> = "a\n".chars().collect();
+        assert_eq!(content.len(), 4);
+        assert_eq!(find_next(&content, &needle, 2), Some(0));
+
+        let needle: Vec<char> = "a\na\n".chars().collect();
+        assert_eq!(find_next(&content, &needle, 0), None);
     }
 }
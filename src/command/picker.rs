@@ -0,0 +1,231 @@
+use super::finder;
+use super::keymap::{Command, Keymap};
+use super::line_edit::LineEdit;
+use super::{Context, State};
+use crate::data::*;
+use crate::view::View;
+use termion::event::{Event, Key};
+
+/// A list of items narrowed down by typing, one of which gets picked
+#[derive(Debug, Clone)]
+pub struct Picker {
+    items: Vec<String>,
+    query: LineEdit,
+    // index in the items matching the query
+    selected: usize,
+    action: PickAction,
+}
+
+// What to do with the item picked
+#[derive(Debug, Clone, Copy)]
+pub enum PickAction {
+    Buffer,
+    File,
+    Recent,
+    Command,
+}
+
+impl PickAction {
+    fn prompt(self) -> &'static str {
+        match self {
+            PickAction::Buffer => "Switch to buffer: ",
+            PickAction::File => "Open file: ",
+            PickAction::Recent => "Open recent file: ",
+            PickAction::Command => "Run command: ",
+        }
+    }
+
+    fn opens_file(self) -> bool {
+        matches!(self, PickAction::File | PickAction::Recent)
+    }
+}
+
+impl Picker {
+    pub fn new(items: Vec<String>, selected: usize, action: PickAction) -> Self {
+        Picker {
+            items,
+            query: LineEdit::default(),
+            selected,
+            action,
+        }
+    }
+
+    // Indices of the items containing the chars of the query in order, best matches first
+    fn matches(&self) -> Vec<usize> {
+        if self.query.is_empty() {
+            return (0..self.items.len()).collect();
+        }
+        let query: Vec<char> = self.query.text().to_lowercase().chars().collect();
+        let mut scored: Vec<(usize, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| score(&query, item).map(|s| (i, s)))
+            .collect();
+        // shorter items go first among equally good matches
+        scored.sort_by_key(|&(i, s)| (std::cmp::Reverse(s), self.items[i].len(), i));
+        scored.into_iter().map(|(i, _)| i).collect()
+    }
+
+    // Shows the items matching the query, or the paths it can be completed to, and turns into a
+    // state
+    pub fn show(self, view: &mut View) -> State {
+        self.query.show(view, self.action.prompt());
+        let matches = self.matches();
+        if !self.query.is_completing() {
+            let items = matches.iter().map(|&i| self.items[i].clone()).collect();
+            view.show_list(items, self.selected);
+        }
+        if self.action.opens_file() {
+            let preview = match self.picked(&matches) {
+                Some(path) => finder::preview(&path),
+                None => None,
+            };
+            view.show_preview(preview);
+        }
+        State::Pick(self)
+    }
+
+    // Path of the file to open, a completed path coming before the matches
+    fn picked(&self, matches: &[usize]) -> Option<String> {
+        if self.query.is_completing() {
+            Some(self.query.text())
+        } else {
+            matches.get(self.selected).map(|&i| self.items[i].clone())
+        }
+    }
+}
+
+impl State {
+    // Lets the user pick one of the commands, listed with the keys they are bound to
+    pub(super) fn pick_command(view: &mut View, keymap: &Keymap) -> Self {
+        let items = Command::all()
+            .map(|command| {
+                format!(
+                    "{:<24}{}",
+                    command.name(),
+                    keymap.keys_for(command).join(", ")
+                )
+            })
+            .collect();
+        Picker::new(items, 0, PickAction::Command).show(view)
+    }
+
+    pub(super) fn handle_pick<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        mut picker: Picker,
+    ) -> Self
+    where
+        T: Editable + Named + Undoable + Modifiable + Saveable + Selectable,
+    {
+        let query = picker.query.text();
+        if picker.query.edit(&event) {
+            if picker.query.text() != query {
+                picker.selected = 0;
+            }
+            return picker.show(view);
+        }
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                view.hide_list();
+                view.quiet();
+                match picker.action {
+                    PickAction::Buffer => match picker.matches().get(picker.selected) {
+                        Some(&i) => State::SwitchBuffer(i),
+                        None => State::Insert,
+                    },
+                    PickAction::Command => match picker.matches().get(picker.selected) {
+                        Some(&i) => {
+                            let command = Command::all().nth(i).unwrap();
+                            State::run_command(command, content, view, ctx)
+                        }
+                        None if content.sel().is_some() => State::Selected,
+                        None => State::Insert,
+                    },
+                    PickAction::File | PickAction::Recent => match picker.picked(&picker.matches())
+                    {
+                        Some(path) => State::Open(path),
+                        // a file that doesn't exist yet
+                        None if !picker.query.is_empty() => State::Open(query),
+                        None => State::Insert,
+                    },
+                }
+            }
+            Event::Key(Key::Esc) | Event::Key(Key::Ctrl('q')) => {
+                view.hide_list();
+                view.quiet();
+                // the palette may have been opened on a selection
+                if content.sel().is_some() {
+                    State::Selected
+                } else {
+                    State::Insert
+                }
+            }
+            Event::Key(Key::Char('\t')) | Event::Key(Key::BackTab)
+                if picker.action.opens_file() =>
+            {
+                picker
+                    .query
+                    .complete_path(event == Event::Key(Key::Char('\t')));
+                picker.selected = 0;
+                picker.show(view)
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Ctrl('p')) if picker.query.is_completing() => {
+                picker.query.complete_path(false);
+                picker.show(view)
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Ctrl('n')) if picker.query.is_completing() => {
+                picker.query.complete_path(true);
+                picker.show(view)
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Ctrl('p')) => {
+                picker.selected = picker.selected.saturating_sub(1);
+                picker.show(view)
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Ctrl('n')) => {
+                if picker.selected + 1 < picker.matches().len() {
+                    picker.selected += 1;
+                }
+                picker.show(view)
+            }
+            Event::Key(Key::Ctrl('v')) => {
+                picker.query.insert(&ctx.clipboard.paste());
+                picker.selected = 0;
+                picker.show(view)
+            }
+            _ => State::Pick(picker),
+        }
+    }
+}
+
+// How well the query matches the item, rewarding chars that follow one another or start a word
+fn score(query: &[char], item: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<char> = None;
+    let mut consecutive = false;
+    for c in item.chars().flat_map(char::to_lowercase) {
+        if next < query.len() && c == query[next] {
+            score += 1;
+            if consecutive {
+                score += 4;
+            }
+            if previous.is_none_or(|p| !p.is_alphanumeric()) {
+                score += 3;
+            }
+            next += 1;
+            consecutive = true;
+        } else {
+            consecutive = false;
+        }
+        previous = Some(c);
+    }
+    if next == query.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,224 @@
+use super::line_edit::LineEdit;
+use super::{PromptAction, State};
+use crate::data::*;
+use crate::view::View;
+use regex::{Regex, RegexBuilder};
+use std::cmp;
+use std::collections::VecDeque;
+use termion::event::{Event, Key};
+
+const CONFIRM_PROMPT: &str = "Replace? (y)es (n)o (a)ll (q)uit: ";
+
+/// Matches of a regex waiting for the user to confirm their replacement
+#[derive(Debug, Clone)]
+pub struct Replace {
+    // chars of the original text matched, along with what replaces them
+    matches: VecDeque<(usize, usize, String)>,
+    // how much the text before the next match grew with the replacements done so far
+    shift: isize,
+    count: usize,
+}
+
+impl State {
+    pub(super) fn handle_replace<T>(
+        content: &mut T,
+        view: &mut View,
+        event: Event,
+        mut replace: Replace,
+    ) -> Self
+    where
+        T: Editable + Selectable + Undoable,
+    {
+        match event {
+            Event::Key(Key::Char('y')) => {
+                replace.replace_next(content);
+                next_match(content, view, replace)
+            }
+            Event::Key(Key::Char('n')) => {
+                replace.matches.pop_front();
+                next_match(content, view, replace)
+            }
+            Event::Key(Key::Char('a')) => {
+                while !replace.matches.is_empty() {
+                    replace.replace_next(content);
+                }
+                finish(content, view, replace)
+            }
+            Event::Key(Key::Char('q')) | Event::Key(Key::Esc) | Event::Key(Key::Ctrl('q')) => {
+                finish(content, view, replace)
+            }
+            _ => State::Prompt(
+                CONFIRM_PROMPT.to_string(),
+                LineEdit::default(),
+                PromptAction::ConfirmReplace(replace),
+            ),
+        }
+    }
+}
+
+impl Replace {
+    fn replace_next<T>(&mut self, content: &mut T)
+    where
+        T: Editable + Selectable,
+    {
+        let (beg, end, replacement) = match self.matches.pop_front() {
+            Some(m) => m,
+            None => return,
+        };
+        let beg = (beg as isize + self.shift) as usize;
+        let end = (end as isize + self.shift) as usize;
+        content.reset_sel();
+        content.move_to(end);
+        for _ in beg..end {
+            content.delete();
+        }
+        for c in replacement.chars() {
+            content.insert(c);
+        }
+        self.shift += replacement.chars().count() as isize - (end - beg) as isize;
+        self.count += 1;
+    }
+}
+
+// Opens the prompt for a pattern to replace in the selection, or in the whole text
+pub(super) fn start_replace<T>(content: &mut T, view: &mut View) -> State
+where
+    T: Editable + Selectable,
+{
+    let scope = match *content.sel() {
+        Some(sel) if !sel.is_empty() => Some(sel),
+        _ => None,
+    };
+    content.reset_sel();
+    content.reset_cursors();
+    let prompt = match scope {
+        Some(_) => "Replace in selection (regex): ",
+        None => "Replace (regex): ",
+    }
+    .to_string();
+    view.prompt(&prompt, "");
+    State::Prompt(prompt, LineEdit::default(), PromptAction::Replace(scope))
+}
+
+// Checks the pattern and asks for its replacement
+pub(super) fn ask_replacement(view: &mut View, pattern: String, scope: Option<Selection>) -> State {
+    if let Err(e) = compile(&pattern) {
+        view.message(&e.to_string());
+        return State::Message;
+    }
+    let prompt = format!("Replace {} with ($1 for groups): ", pattern);
+    view.prompt(&prompt, "");
+    State::Prompt(
+        prompt,
+        LineEdit::default(),
+        PromptAction::ReplaceWith(pattern, scope),
+    )
+}
+
+// Finds every match and goes through them, all of the replacements being undone at once
+pub(super) fn confirm_replace<T>(
+    content: &mut T,
+    view: &mut View,
+    pattern: &str,
+    replacement: &str,
+    scope: Option<Selection>,
+) -> State
+where
+    T: Editable + Selectable + Undoable,
+{
+    let regex = match compile(pattern) {
+        Ok(regex) => regex,
+        Err(e) => {
+            view.message(&e.to_string());
+            return State::Message;
+        }
+    };
+    let text: String = content.iter().collect();
+    // the final line break is kept out of reach
+    let (beg, end) = match scope {
+        Some(sel) => (sel.beg(), cmp::min(sel.end(), content.len() - 1)),
+        None => (0, content.len() - 1),
+    };
+    let matches = find_matches(&regex, &text, beg, end, replacement);
+    if matches.is_empty() {
+        view.message(&format!("No match for {}", pattern));
+        return State::Message;
+    }
+
+    content.begin_group();
+    let replace = Replace {
+        matches,
+        shift: 0,
+        count: 0,
+    };
+    next_match(content, view, replace)
+}
+
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).multi_line(true).build()
+}
+
+// Matches of regex between the chars beg and end of text, with their expanded replacement
+fn find_matches(
+    regex: &Regex,
+    text: &str,
+    beg: usize,
+    end: usize,
+    replacement: &str,
+) -> VecDeque<(usize, usize, String)> {
+    let byte_at = |pos: usize| text.char_indices().nth(pos).map_or(text.len(), |(b, _)| b);
+    let byte_beg = byte_at(beg);
+    let scoped = &text[byte_beg..byte_at(end)];
+
+    // regex positions are in bytes, walk through the text once to turn them into chars
+    let mut matches = VecDeque::new();
+    let mut chars = beg;
+    let mut bytes = 0;
+    for caps in regex.captures_iter(scoped) {
+        let m = caps.get(0).unwrap();
+        chars += scoped[bytes..m.start()].chars().count();
+        let match_beg = chars;
+        chars += m.as_str().chars().count();
+        bytes = m.end();
+
+        let mut expanded = String::new();
+        caps.expand(replacement, &mut expanded);
+        matches.push_back((match_beg, chars, expanded));
+    }
+    matches
+}
+
+// Shows the next match to be confirmed, if any is left
+fn next_match<T>(content: &mut T, view: &mut View, replace: Replace) -> State
+where
+    T: Editable + Selectable + Undoable,
+{
+    let (beg, end) = match replace.matches.front() {
+        Some(&(beg, end, _)) => (
+            (beg as isize + replace.shift) as usize,
+            (end as isize + replace.shift) as usize,
+        ),
+        None => return finish(content, view, replace),
+    };
+    content.move_to(end);
+    content.set_sel(Selection::new(beg, end));
+    view.adjust_view(content.line());
+    view.prompt(CONFIRM_PROMPT, "");
+    State::Prompt(
+        CONFIRM_PROMPT.to_string(),
+        LineEdit::default(),
+        PromptAction::ConfirmReplace(replace),
+    )
+}
+
+fn finish<T>(content: &mut T, view: &mut View, replace: Replace) -> State
+where
+    T: Editable + Selectable + Undoable,
+{
+    content.end_group();
+    content.reset_sel();
+    view.adjust_view(content.line());
+    let plural = if replace.count == 1 { "" } else { "s" };
+    view.message(&format!("Replaced {} occurrence{}", replace.count, plural));
+    State::Message
+}
@@ -0,0 +1,122 @@
+use super::line_edit::LineEdit;
+use super::{PromptAction, State};
+use crate::data::*;
+use crate::view::View;
+use std::fs;
+use std::io::ErrorKind;
+
+// Asks where to write the content, starting from its current name
+pub(super) fn start_save_as<T: Named>(content: &T, view: &mut View, action: PromptAction) -> State {
+    let prompt = match action {
+        PromptAction::Save => "Save to: ",
+        PromptAction::Rename => "Rename to: ",
+        _ => "Save as: ",
+    }
+    .to_string();
+    let mut line = LineEdit::default();
+    line.set_text(content.name());
+    line.show(view, &prompt);
+    State::Prompt(prompt, line, action)
+}
+
+// Writes the content to a new file, which it is then named after
+pub(super) fn save_as<T: Saveable>(content: &mut T, view: &mut View, name: String) -> State {
+    match write_to(content, name) {
+        Ok(_) => view.message(&format!("Saved file {}", content.name())),
+        Err(e) => view.message(&e),
+    }
+    State::Message
+}
+
+// Writes the content to a new file, going on to the next state once it is saved
+pub(super) fn save_then<T: Saveable>(
+    content: &mut T,
+    view: &mut View,
+    name: String,
+    next: State,
+) -> State {
+    match write_to(content, name) {
+        Ok(_) => next,
+        Err(e) => {
+            view.message(&e);
+            State::Message
+        }
+    }
+}
+
+// Writes the content to a new file and removes the one it came from
+pub(super) fn rename<T: Saveable>(content: &mut T, view: &mut View, name: String) -> State {
+    let old_name = content.name().clone();
+    if let Err(e) = write_to(content, name) {
+        view.message(&e);
+        return State::Message;
+    }
+    let msg = if old_name.is_empty() || same_file(&old_name, content.name()) {
+        format!("Saved file {}", content.name())
+    } else {
+        match fs::remove_file(&old_name) {
+            Ok(_) => format!("Renamed {} to {}", old_name, content.name()),
+            // never saved, so there was nothing to rename
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                format!("Saved file {}", content.name())
+            }
+            Err(e) => format!(
+                "Saved file {} but couldn't remove {}: {}",
+                content.name(),
+                old_name,
+                e
+            ),
+        }
+    };
+    view.message(&msg);
+    State::Message
+}
+
+// Writes the content to another file, keeping its name and its changes unsaved
+pub(super) fn write_copy<T: Editable>(content: &T, name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("No file name given".to_string());
+    }
+    let text: String = content.iter().collect();
+    fs::write(name, text).map_err(|e| e.to_string())
+}
+
+// Names the content and saves it, keeping the old name if it can't be saved
+fn write_to<T: Saveable>(content: &mut T, name: String) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("No file name given".to_string());
+    }
+    let old_name = content.name().clone();
+    content.set_name(name);
+    content.save().map_err(|e| {
+        content.set_name(old_name);
+        e.to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn copy_keeps_name_and_changes() {
+        let dir = env::temp_dir().join(format!("smith-copy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let copy = dir.join("copy.txt");
+        let mut text = Text::empty();
+        text.set_name("original.txt".to_string());
+        for c in "some text".chars() {
+            text.insert(c);
+        }
+
+        write_copy(&text, copy.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "some text\n");
+        assert_eq!(text.name(), "original.txt");
+        assert!(text.was_modified());
+        assert!(write_copy(&text, "").is_err());
+        assert!(write_copy(&text, dir.join("nowhere/copy.txt").to_str().unwrap()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
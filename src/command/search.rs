@@ -0,0 +1,236 @@
+use super::line_edit::LineEdit;
+use super::{Context, PromptAction, State};
+use crate::data::*;
+use crate::view::View;
+use termion::event::{Event, Key};
+
+impl State {
+    pub(super) fn handle_search<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        mut line: LineEdit,
+        mut search: Search,
+        origin: usize,
+    ) -> Self
+    where
+        T: Editable + Selectable,
+    {
+        if line.edit(&event) {
+            if line.text() != search.query {
+                search.query = line.text();
+                search_from(content, view, &search, origin, true);
+            }
+            return search_state(view, line, search, origin);
+        }
+        match event {
+            Event::Key(Key::Char('\n')) => {
+                view.set_search(None);
+                view.quiet();
+                ctx.search_history.push(search.query.clone());
+                if search.query.is_empty() {
+                    return State::Insert;
+                }
+                ctx.last_search = Some(search);
+                if content.sel().is_some() {
+                    State::Selected
+                } else {
+                    view.message("No match");
+                    State::Message
+                }
+            }
+            Event::Key(Key::Esc) | Event::Key(Key::Ctrl('q')) => {
+                // back to where we were before searching
+                view.set_search(None);
+                view.quiet();
+                content.reset_sel();
+                content.move_to(origin);
+                view.adjust_view(content.line());
+                State::Insert
+            }
+            Event::Key(Key::Ctrl('v')) => {
+                line.insert(&ctx.clipboard.paste());
+                search.query = line.text();
+                search_from(content, view, &search, origin, true);
+                search_state(view, line, search, origin)
+            }
+            Event::Key(Key::Ctrl('n')) | Event::Key(Key::F(3)) => {
+                find_again(content, view, &search, true);
+                search_state(view, line, search, origin)
+            }
+            Event::Key(Key::Ctrl('p')) => {
+                find_again(content, view, &search, false);
+                search_state(view, line, search, origin)
+            }
+            Event::Unsupported(ref u) if is_shift_f3(u) => {
+                find_again(content, view, &search, false);
+                search_state(view, line, search, origin)
+            }
+            Event::Key(Key::Alt('c')) => {
+                search.case_sensitive = !search.case_sensitive;
+                search_from(content, view, &search, origin, true);
+                search_state(view, line, search, origin)
+            }
+            Event::Key(Key::Alt('w')) => {
+                search.whole_word = !search.whole_word;
+                search_from(content, view, &search, origin, true);
+                search_state(view, line, search, origin)
+            }
+            Event::Key(Key::Up) => {
+                if let Some(query) = ctx.search_history.older(&search.query) {
+                    search.query = query.clone();
+                    line.set_text(query);
+                    search_from(content, view, &search, origin, true);
+                }
+                search_state(view, line, search, origin)
+            }
+            Event::Key(Key::Down) => {
+                if let Some(query) = ctx.search_history.newer(&search.query) {
+                    line.set_text(&query);
+                    search.query = query;
+                    search_from(content, view, &search, origin, true);
+                }
+                search_state(view, line, search, origin)
+            }
+            _ => search_state(view, line, search, origin),
+        }
+    }
+}
+
+// Opens the search prompt, with the options of the previous search
+pub(super) fn start_search<T>(content: &mut T, view: &mut View, ctx: &Context) -> State
+where
+    T: Editable + Selectable,
+{
+    content.reset_cursors();
+    content.reset_sel();
+    let search = Search {
+        query: String::new(),
+        ..ctx.last_search.clone().unwrap_or_default()
+    };
+    search_state(view, LineEdit::default(), search, content.pos())
+}
+
+// Goes to the next or previous match of the last search
+pub(super) fn repeat_search<T>(
+    content: &mut T,
+    view: &mut View,
+    ctx: &Context,
+    forward: bool,
+) -> State
+where
+    T: Editable + Selectable,
+{
+    let search = match ctx.last_search {
+        Some(ref search) => search.clone(),
+        None => {
+            view.message("Nothing to search for");
+            return State::Message;
+        }
+    };
+    content.reset_cursors();
+    if find_again(content, view, &search, forward) {
+        State::Selected
+    } else {
+        view.message(&format!("No match for {}", search.query));
+        State::Message
+    }
+}
+
+// Goes to the next or previous occurrence of the selection, or of the word under the cursor
+pub(super) fn goto_occurrence<T>(content: &mut T, view: &mut View, forward: bool) -> State
+where
+    T: Editable + Selectable,
+{
+    let (search, current) = match Search::under_cursor(content) {
+        Some(occurrence) => occurrence,
+        None => return State::Insert,
+    };
+    let matches = search.find_all(content);
+    let from = if forward {
+        current.beg() + 1
+    } else {
+        current.beg()
+    };
+    let (beg, end) = match Search::next_match(&matches, from, forward) {
+        Some(i) => matches[i],
+        None => return State::Insert,
+    };
+    content.reset_cursors();
+    if content.sel().is_some() {
+        content.move_to(end);
+        content.set_sel(Selection::new(beg, end));
+        view.adjust_view(content.line());
+        State::Selected
+    } else {
+        // stay at the same place in the word
+        let offset = content.pos() - current.beg();
+        content.move_to(beg + offset);
+        view.adjust_view(content.line());
+        State::Insert
+    }
+}
+
+pub(super) fn is_shift_f3(seq: &[u8]) -> bool {
+    match super::input::parse_modified_key(seq) {
+        Some((Key::F(3), modifiers)) => modifiers.shift,
+        _ => false,
+    }
+}
+
+fn search_state(view: &mut View, line: LineEdit, search: Search, origin: usize) -> State {
+    let mut prompt = "Search".to_string();
+    if search.case_sensitive {
+        prompt.push_str(" [Aa]");
+    }
+    if search.whole_word {
+        prompt.push_str(" [word]");
+    }
+    prompt.push_str(": ");
+    line.show(view, &prompt);
+    view.set_search(Some(search.clone()));
+    State::Prompt(prompt, line, PromptAction::Search(search, origin))
+}
+
+// Moves to the match following the current one, or preceding it
+fn find_again<T>(content: &mut T, view: &mut View, search: &Search, forward: bool) -> bool
+where
+    T: Editable + Selectable,
+{
+    let pos = match *content.sel() {
+        Some(sel) if forward => sel.beg() + 1,
+        Some(sel) => sel.beg(),
+        None => content.pos(),
+    };
+    search_from(content, view, search, pos, forward)
+}
+
+// Selects the first match from pos in the given direction, and tells whether there was one
+fn search_from<T>(
+    content: &mut T,
+    view: &mut View,
+    search: &Search,
+    pos: usize,
+    forward: bool,
+) -> bool
+where
+    T: Editable + Selectable,
+{
+    let matches = search.find_all(content);
+    match Search::next_match(&matches, pos, forward) {
+        Some(i) => {
+            let (beg, end) = matches[i];
+            content.move_to(end);
+            content.set_sel(Selection::new(beg, end));
+            view.adjust_view(content.line());
+            true
+        }
+        None => {
+            content.reset_sel();
+            content.move_to(pos);
+            view.adjust_view(content.line());
+            false
+        }
+    }
+}
@@ -0,0 +1,1311 @@
+use super::keymap::Lookup;
+use super::{ex, new_line, search, Context, State};
+use crate::data::*;
+use crate::view::{CursorShape, View};
+use std::cmp;
+use termion::event::{Event, Key};
+
+/// What modal editing keeps from one key to the next
+#[derive(Debug, Default)]
+pub struct Vi {
+    // in insert mode rather than the normal one
+    inserting: bool,
+    // keys of the change being made, until insert mode is left
+    change: Option<Vec<Key>>,
+    // keys of the last change, which . repeats
+    last_change: Vec<Key>,
+}
+
+impl Vi {
+    // Goes back to normal mode when another pane or buffer gets the keys, the change being made
+    // ending with keys which can't be replayed
+    pub fn leave_insert(&mut self) {
+        self.inserting = false;
+        self.change = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// Keys of a command typed so far in normal or visual mode
+#[derive(Debug, Clone, Default)]
+pub struct Pending {
+    count: Option<usize>,
+    operator: Option<Operator>,
+    // count typed after the operator, multiplying the first one
+    motion_count: Option<usize>,
+    // waiting for the second g of gg
+    g: bool,
+    keys: Vec<Key>,
+}
+
+/// Visual mode, selecting from an anchor to the cursor
+#[derive(Debug, Clone)]
+pub struct Visual {
+    anchor: usize,
+    // whole lines rather than chars
+    lines: bool,
+    pending: Pending,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    // to the start, the end or back to the start of words, of anything but blanks when big
+    WordStart(bool),
+    WordEnd(bool),
+    WordBack(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+}
+
+// How the text between the cursor and where a motion takes it is operated on
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+// Text an operator acts on, as chars from the first to one past the last, or as lines
+#[derive(Debug, Clone, Copy)]
+enum Span {
+    Chars(usize, usize),
+    Lines(usize, usize),
+}
+
+impl Pending {
+    fn count(&self) -> Option<usize> {
+        match (self.count, self.motion_count) {
+            (None, None) => None,
+            (first, second) => Some(first.unwrap_or(1) * second.unwrap_or(1)),
+        }
+    }
+
+    // Takes a digit as part of a count, 0 being a motion unless a count was started
+    fn push_digit(&mut self, key: Key) -> bool {
+        let digit = match key {
+            Key::Char(c) => c.to_digit(10),
+            _ => None,
+        };
+        let count = if self.operator.is_some() {
+            &mut self.motion_count
+        } else {
+            &mut self.count
+        };
+        match digit {
+            Some(d) if d > 0 || count.is_some() => {
+                *count = Some(
+                    count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(d as usize),
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl State {
+    // Turns the states shared with modeless editing into those of the current mode
+    pub fn in_mode<T>(self, content: &mut T, ctx: &Context) -> Self
+    where
+        T: Editable + Selectable,
+    {
+        let inserting = match ctx.vi {
+            Some(ref vi) => vi.inserting,
+            None => return self,
+        };
+        match self {
+            State::Insert | State::Pasted(..) | State::ViInsert if inserting => State::ViInsert,
+            State::Insert | State::Pasted(..) | State::ViInsert => {
+                content.reset_sel();
+                clamp(content);
+                State::Normal(Pending::default())
+            }
+            State::Selected => match *content.sel() {
+                Some(sel) if !sel.is_empty() => State::Visual(Visual::from_selection(content, sel)),
+                _ => {
+                    content.reset_sel();
+                    State::Normal(Pending::default())
+                }
+            },
+            state => state,
+        }
+    }
+
+    // Name of the mode shown in the status bar, and the shape of the cursor in it
+    pub fn mode(&self, ctx: &Context) -> Option<(&'static str, CursorShape)> {
+        let vi = ctx.vi.as_ref()?;
+        let normal = ("NORMAL", CursorShape::Block);
+        let insert = ("INSERT", CursorShape::Bar);
+        match *self {
+            State::Normal(_) => Some(normal),
+            State::ViInsert | State::Paste(_) => Some(insert),
+            State::Message | State::Chord(_) if vi.inserting => Some(insert),
+            State::Message | State::Chord(_) => Some(normal),
+            State::Visual(ref visual) if visual.lines => Some(("V-LINE", CursorShape::Block)),
+            State::Visual(_) | State::Select(_) => Some(("VISUAL", CursorShape::Block)),
+            State::SelectBlock(..) => Some(("V-BLOCK", CursorShape::Block)),
+            _ => None,
+        }
+    }
+
+    pub(super) fn handle_normal<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        mut pending: Pending,
+    ) -> Self
+    where
+        T: Editable + Saveable + Undoable + Selectable + Modifiable,
+    {
+        // the mouse and escape sequences work as they do when inserting
+        let key = match event {
+            Event::Key(key) => key,
+            _ => return State::handle_insert(content, view, ctx, event),
+        };
+        pending.keys.push(key);
+        if pending.push_digit(key) {
+            return State::Normal(pending);
+        }
+        let count = pending.count();
+        let n = count.unwrap_or(1);
+        let after_g = pending.g;
+        if let Some(motion) = motion(key, &mut pending) {
+            let motion = match (pending.operator, motion) {
+                // cw changes up to the end of the word, leaving the blanks after it
+                (Some(Operator::Change), Motion::WordStart(big))
+                    if !is_blank(char_under(content)) =>
+                {
+                    Motion::WordEnd(big)
+                }
+                (_, motion) => motion,
+            };
+            return match pending.operator {
+                Some(op) => operate(content, view, ctx, op, motion, count, pending.keys),
+                None => {
+                    move_by(content, motion, count, false);
+                    clamp(content);
+                    view.adjust_view(content.line());
+                    State::Normal(Pending::default())
+                }
+            };
+        }
+        if pending.g {
+            return State::Normal(pending);
+        }
+        if after_g {
+            return State::Normal(Pending::default());
+        }
+
+        let keys = pending.keys.clone();
+        if let Some(op) = pending.operator {
+            return match (op, key) {
+                // doubled, the operator acts on whole lines
+                (Operator::Delete, Key::Char('d'))
+                | (Operator::Change, Key::Char('c'))
+                | (Operator::Yank, Key::Char('y')) => {
+                    let first = content.line();
+                    let last = cmp::min(first + n - 1, content.line_count() - 1);
+                    apply(content, view, ctx, op, Span::Lines(first, last), keys)
+                }
+                _ => State::Normal(Pending::default()),
+            };
+        }
+        match key {
+            Key::Char('d') | Key::Char('c') | Key::Char('y') => {
+                pending.operator = Some(match key {
+                    Key::Char('d') => Operator::Delete,
+                    Key::Char('c') => Operator::Change,
+                    _ => Operator::Yank,
+                });
+                State::Normal(pending)
+            }
+            Key::Char('D') => operate(
+                content,
+                view,
+                ctx,
+                Operator::Delete,
+                Motion::LineEnd,
+                count,
+                keys,
+            ),
+            Key::Char('C') => operate(
+                content,
+                view,
+                ctx,
+                Operator::Change,
+                Motion::LineEnd,
+                count,
+                keys,
+            ),
+            Key::Char('s') => operate(
+                content,
+                view,
+                ctx,
+                Operator::Change,
+                Motion::Right,
+                count,
+                keys,
+            ),
+            Key::Char('S') => {
+                let first = content.line();
+                let last = cmp::min(first + n - 1, content.line_count() - 1);
+                apply(
+                    content,
+                    view,
+                    ctx,
+                    Operator::Change,
+                    Span::Lines(first, last),
+                    keys,
+                )
+            }
+            Key::Char('x') | Key::Delete => operate(
+                content,
+                view,
+                ctx,
+                Operator::Delete,
+                Motion::Right,
+                count,
+                keys,
+            ),
+            Key::Char('X') => operate(
+                content,
+                view,
+                ctx,
+                Operator::Delete,
+                Motion::Left,
+                count,
+                keys,
+            ),
+            Key::Char('p') | Key::Char('P') => {
+                put(content, ctx, key == Key::Char('P'), n);
+                view.adjust_view(content.line());
+                done(ctx, keys);
+                State::Normal(Pending::default())
+            }
+            Key::Char('u') | Key::Ctrl('r') => {
+                for _ in 0..n {
+                    if key == Key::Char('u') {
+                        content.undo();
+                    } else {
+                        content.redo();
+                    }
+                }
+                content.reset_sel();
+                clamp(content);
+                view.adjust_view(content.line());
+                State::Normal(Pending::default())
+            }
+            Key::Char('i')
+            | Key::Char('a')
+            | Key::Char('I')
+            | Key::Char('A')
+            | Key::Char('o')
+            | Key::Char('O')
+            | Key::Insert => {
+                content.reset_sel();
+                content.reset_cursors();
+                open_insert(content, view, key);
+                start_insert(ctx, keys)
+            }
+            Key::Char('v') | Key::Char('V') => {
+                let visual = Visual {
+                    anchor: content.pos(),
+                    lines: key == Key::Char('V'),
+                    pending: Pending::default(),
+                };
+                select(content, &visual);
+                State::Visual(visual)
+            }
+            Key::Char('.') => repeat(content, view, ctx, count),
+            Key::Char(':') => {
+                // a count gives the range of as many lines
+                let range = match count {
+                    Some(n) if n > 1 => format!(".,.+{}", n - 1),
+                    _ => String::new(),
+                };
+                ex::start_command_line(view, &range)
+            }
+            Key::Char('/') => search::start_search(content, view, ctx),
+            Key::Char('n') | Key::Char('N') => {
+                match search::repeat_search(content, view, ctx, key == Key::Char('n')) {
+                    State::Selected => {
+                        leave_match(content);
+                        State::Normal(Pending::default())
+                    }
+                    state => state,
+                }
+            }
+            Key::Esc => {
+                content.reset_sel();
+                content.reset_cursors();
+                view.quiet();
+                State::Normal(Pending::default())
+            }
+            // the keys typing text are left alone, the others being those of the keymap
+            Key::Char(_) => State::Normal(Pending::default()),
+            _ => State::handle_insert(content, view, ctx, event),
+        }
+    }
+
+    pub(super) fn handle_vi_insert<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+    ) -> Self
+    where
+        T: Editable + Saveable + Undoable + Selectable + Modifiable,
+    {
+        let vi = match ctx.vi {
+            Some(ref mut vi) => vi,
+            None => return State::handle_insert(content, view, ctx, event),
+        };
+        if let (Event::Key(key), Some(change)) = (&event, vi.change.as_mut()) {
+            change.push(*key);
+        }
+        if event != Event::Key(Key::Esc) {
+            return State::handle_insert(content, view, ctx, event);
+        }
+        vi.inserting = false;
+        if let Some(change) = vi.change.take() {
+            vi.last_change = change;
+        }
+        // the cursor goes back onto the last char typed
+        if content.col() > 0 {
+            content.step(Movement::Left);
+        }
+        view.adjust_view(content.line());
+        State::Normal(Pending::default())
+    }
+
+    pub(super) fn handle_visual<T>(
+        content: &mut T,
+        view: &mut View,
+        ctx: &mut Context,
+        event: Event,
+        mut visual: Visual,
+    ) -> Self
+    where
+        T: Editable + Saveable + Undoable + Selectable + Modifiable,
+    {
+        let key = match event {
+            Event::Key(key) => key,
+            _ => {
+                return match State::handle_insert(content, view, ctx, event) {
+                    // such as scrolling, which keeps the selection
+                    State::Insert if content.sel().is_some() => State::Visual(visual),
+                    state => state,
+                };
+            }
+        };
+        if visual.pending.push_digit(key) {
+            return State::Visual(visual);
+        }
+        let count = visual.pending.count();
+        let after_g = visual.pending.g;
+        if let Some(motion) = motion(key, &mut visual.pending) {
+            visual.pending = Pending::default();
+            move_by(content, motion, count, false);
+            clamp(content);
+            select(content, &visual);
+            view.adjust_view(content.line());
+            return State::Visual(visual);
+        }
+        if visual.pending.g {
+            return State::Visual(visual);
+        }
+        visual.pending = Pending::default();
+        if after_g {
+            return State::Visual(visual);
+        }
+
+        match key {
+            Key::Char('o') => {
+                let anchor = visual.anchor;
+                visual.anchor = content.pos();
+                content.move_to(anchor);
+                select(content, &visual);
+                view.adjust_view(content.line());
+                State::Visual(visual)
+            }
+            Key::Char('v') | Key::Char('V') if visual.lines != (key == Key::Char('V')) => {
+                visual.lines = !visual.lines;
+                select(content, &visual);
+                State::Visual(visual)
+            }
+            Key::Char('v') | Key::Char('V') | Key::Esc => {
+                content.reset_sel();
+                State::Normal(Pending::default())
+            }
+            Key::Char('d') | Key::Char('x') | Key::Delete => {
+                operate_visual(content, view, ctx, &visual, Operator::Delete, false)
+            }
+            Key::Char('c') | Key::Char('s') => {
+                operate_visual(content, view, ctx, &visual, Operator::Change, false)
+            }
+            Key::Char('y') => operate_visual(content, view, ctx, &visual, Operator::Yank, false),
+            Key::Char('D') | Key::Char('X') => {
+                operate_visual(content, view, ctx, &visual, Operator::Delete, true)
+            }
+            Key::Char('C') | Key::Char('S') => {
+                operate_visual(content, view, ctx, &visual, Operator::Change, true)
+            }
+            Key::Char('Y') => operate_visual(content, view, ctx, &visual, Operator::Yank, true),
+            Key::Char(':') => {
+                let (first, last) = visual_lines(content, &visual);
+                content.reset_sel();
+                ex::start_command_line(view, &format!("{},{}", first + 1, last + 1))
+            }
+            Key::Char(_) => State::Visual(visual),
+            _ => match ctx.keymap.lookup(&[key]) {
+                Lookup::Command(command) => State::run_command(command, content, view, ctx),
+                Lookup::Prefix => State::start_chord(view, vec![key]),
+                Lookup::Unbound => State::Visual(visual),
+            },
+        }
+    }
+}
+
+impl Visual {
+    // Takes over a selection made some other way, the cursor going onto its last char
+    fn from_selection<T>(content: &mut T, sel: Selection) -> Self
+    where
+        T: Editable + Selectable,
+    {
+        let (anchor, head) = if sel.head > sel.anchor {
+            (sel.beg(), sel.end() - 1)
+        } else {
+            (sel.end() - 1, sel.beg())
+        };
+        content.move_to(head);
+        let visual = Visual {
+            anchor,
+            lines: false,
+            pending: Pending::default(),
+        };
+        select(content, &visual);
+        visual
+    }
+}
+
+// Motion bound to the key, remembering a g waiting for another
+fn motion(key: Key, pending: &mut Pending) -> Option<Motion> {
+    if pending.g {
+        pending.g = false;
+        return match key {
+            Key::Char('g') => Some(Motion::FirstLine),
+            _ => None,
+        };
+    }
+    let motion = match key {
+        Key::Char('h') | Key::Left | Key::Backspace | Key::Ctrl('h') => Motion::Left,
+        Key::Char('l') | Key::Right | Key::Char(' ') => Motion::Right,
+        Key::Char('k') | Key::Up => Motion::Up,
+        Key::Char('j') | Key::Down | Key::Char('\n') => Motion::Down,
+        Key::Char('w') => Motion::WordStart(false),
+        Key::Char('W') => Motion::WordStart(true),
+        Key::Char('e') => Motion::WordEnd(false),
+        Key::Char('E') => Motion::WordEnd(true),
+        Key::Char('b') => Motion::WordBack(false),
+        Key::Char('B') => Motion::WordBack(true),
+        Key::Char('0') | Key::Home => Motion::LineStart,
+        Key::Char('^') => Motion::FirstNonBlank,
+        Key::Char('$') | Key::End => Motion::LineEnd,
+        Key::Char('G') => Motion::LastLine,
+        Key::Char('g') => {
+            pending.g = true;
+            return None;
+        }
+        _ => return None,
+    };
+    Some(motion)
+}
+
+// Moves the cursor, telling how the text moved over is operated on, unless it can't go there
+fn move_by<T>(
+    content: &mut T,
+    motion: Motion,
+    count: Option<usize>,
+    operating: bool,
+) -> Option<Kind>
+where
+    T: Editable,
+{
+    let n = count.unwrap_or(1);
+    let line = content.line();
+    let last_line = content.line_count() - 1;
+    let kind = match motion {
+        Motion::Left => {
+            content.move_to(content.pos() - cmp::min(n, content.col()));
+            Kind::Exclusive
+        }
+        Motion::Right => {
+            // an operator can reach the line break, to act on the last char
+            let len = line_len(content, line) - 1;
+            let max = if operating {
+                len
+            } else {
+                len.saturating_sub(1)
+            };
+            content.move_at(line, cmp::min(content.col() + n, max));
+            Kind::Exclusive
+        }
+        Motion::Up | Motion::Down => {
+            let up = matches!(motion, Motion::Up);
+            if (up && n > line) || (!up && line + n > last_line) {
+                return None;
+            }
+            for _ in 0..n {
+                content.step(if up { Movement::Up } else { Movement::Down });
+            }
+            Kind::Linewise
+        }
+        Motion::WordStart(big) => {
+            for i in 0..n {
+                let from = content.line();
+                let to = next_word_start(content, big);
+                content.move_to(to);
+                // the last word of a line is operated on up to its end, not the next line
+                if operating && i == n - 1 && content.line() > from {
+                    content.move_at(from, line_len(content, from) - 1);
+                }
+            }
+            Kind::Exclusive
+        }
+        Motion::WordEnd(big) => {
+            for _ in 0..n {
+                let to = next_word_end(content, big);
+                content.move_to(to);
+            }
+            Kind::Inclusive
+        }
+        Motion::WordBack(big) => {
+            for _ in 0..n {
+                let to = prev_word_start(content, big);
+                content.move_to(to);
+            }
+            Kind::Exclusive
+        }
+        Motion::LineStart => {
+            content.move_at(line, 0);
+            Kind::Exclusive
+        }
+        Motion::FirstNonBlank => {
+            first_non_blank(content, line);
+            Kind::Exclusive
+        }
+        Motion::LineEnd => {
+            // a count goes down as many lines, less one
+            let target = line + n - 1;
+            if target > last_line {
+                return None;
+            }
+            content.move_at(target, line_len(content, target) - 1);
+            Kind::Inclusive
+        }
+        Motion::FirstLine | Motion::LastLine => {
+            let default = if matches!(motion, Motion::FirstLine) {
+                0
+            } else {
+                last_line
+            };
+            let target = count.map_or(default, |n| cmp::min(n.saturating_sub(1), last_line));
+            first_non_blank(content, target);
+            Kind::Linewise
+        }
+    };
+    Some(kind)
+}
+
+// Applies an operator to the text a motion moves over
+fn operate<T>(
+    content: &mut T,
+    view: &mut View,
+    ctx: &mut Context,
+    op: Operator,
+    motion: Motion,
+    count: Option<usize>,
+    keys: Vec<Key>,
+) -> State
+where
+    T: Editable + Selectable + Undoable,
+{
+    content.reset_sel();
+    content.reset_cursors();
+    let (start, start_line) = (content.pos(), content.line());
+    let kind = match move_by(content, motion, count, true) {
+        Some(kind) => kind,
+        None => return State::Normal(Pending::default()),
+    };
+    let pos = content.pos();
+    let (beg, end) = (cmp::min(start, pos), cmp::max(start, pos));
+    let span = match kind {
+        Kind::Linewise => {
+            let line = content.line();
+            Span::Lines(cmp::min(start_line, line), cmp::max(start_line, line))
+        }
+        Kind::Exclusive => Span::Chars(beg, end),
+        // the last char is operated on too, unless it is a line break
+        Kind::Inclusive if char_at(content, end) == '\n' => Span::Chars(beg, end),
+        Kind::Inclusive => Span::Chars(beg, end + 1),
+    };
+    content.move_to(start);
+    apply(content, view, ctx, op, span, keys)
+}
+
+// Deletes, changes or yanks the span, remembering the keys of a change for . to repeat it
+fn apply<T>(
+    content: &mut T,
+    view: &mut View,
+    ctx: &mut Context,
+    op: Operator,
+    span: Span,
+    keys: Vec<Key>,
+) -> State
+where
+    T: Editable + Selectable + Undoable,
+{
+    content.reset_sel();
+    content.reset_cursors();
+    if let (Span::Chars(beg, end), false) = (span, op == Operator::Change) {
+        if beg == end {
+            return State::Normal(Pending::default());
+        }
+    }
+    ctx.clipboard.copy(view, span_text(content, span));
+    match (op, span) {
+        (Operator::Yank, Span::Chars(beg, _)) => content.move_to(beg),
+        (Operator::Yank, Span::Lines(first, _)) => {
+            if content.line() > first {
+                content.move_at(first, content.col());
+            }
+        }
+        (Operator::Delete, _) => {
+            content.begin_group();
+            remove_span(content, span);
+            content.end_group();
+            if let Span::Lines(first, _) = span {
+                first_non_blank(content, cmp::min(first, content.line_count() - 1));
+            }
+            done(ctx, keys);
+        }
+        (Operator::Change, Span::Chars(beg, end)) => {
+            content.begin_group();
+            remove(content, beg, end);
+            content.end_group();
+            view.adjust_view(content.line());
+            return start_insert(ctx, keys);
+        }
+        (Operator::Change, Span::Lines(first, last)) => {
+            // the lines are emptied rather than removed, keeping the indentation if asked to
+            let indent = if view.settings().auto_indent {
+                content
+                    .iter_line(first)
+                    .take_while(|&c| c == ' ' || c == '\t')
+                    .count()
+            } else {
+                0
+            };
+            let beg = content.line_index_to_char_index(first) + indent;
+            let end = content.line_index_to_char_index(last) + line_len(content, last) - 1;
+            content.begin_group();
+            remove(content, beg, cmp::max(beg, end));
+            content.end_group();
+            view.adjust_view(content.line());
+            return start_insert(ctx, keys);
+        }
+    }
+    clamp(content);
+    view.adjust_view(content.line());
+    State::Normal(Pending::default())
+}
+
+// Applies an operator to the selection of visual mode, or to its lines
+fn operate_visual<T>(
+    content: &mut T,
+    view: &mut View,
+    ctx: &mut Context,
+    visual: &Visual,
+    op: Operator,
+    lines: bool,
+) -> State
+where
+    T: Editable + Selectable + Undoable,
+{
+    // . repeats the change on as many lines or chars from the cursor
+    let op_key = Key::Char(match op {
+        Operator::Delete => 'd',
+        Operator::Change => 'c',
+        Operator::Yank => 'y',
+    });
+    let counted = |n: usize, motion: Key| {
+        let mut keys: Vec<Key> = n.to_string().chars().map(Key::Char).collect();
+        keys.extend([op_key, motion]);
+        keys
+    };
+    if lines || visual.lines {
+        let (first, last) = visual_lines(content, visual);
+        let keys = counted(last - first + 1, op_key);
+        return apply(content, view, ctx, op, Span::Lines(first, last), keys);
+    }
+    // the selection is used as is, as there may be more than one
+    let beg = content.sel().map_or(content.pos(), |sel| sel.beg());
+    let sel = *content.sel();
+    let keys = match sel {
+        Some(sel) if content.cursors().is_empty() && within_line(content, sel) => {
+            counted(sel.end() - sel.beg(), Key::Char('l'))
+        }
+        // nothing . could do the same way
+        _ => Vec::new(),
+    };
+    ctx.clipboard.copy(view, content.sel_text());
+    match op {
+        Operator::Yank => {
+            content.reset_sel();
+            content.reset_cursors();
+            content.move_to(beg);
+        }
+        Operator::Delete | Operator::Change => {
+            content.begin_group();
+            content.delete_sel();
+            content.end_group();
+            content.reset_sel();
+            view.adjust_view(content.line());
+            if op == Operator::Change {
+                return start_insert(ctx, keys);
+            }
+            done(ctx, keys);
+        }
+    }
+    clamp(content);
+    view.adjust_view(content.line());
+    State::Normal(Pending::default())
+}
+
+// Moves the cursor where the text typed in insert mode goes
+fn open_insert<T>(content: &mut T, view: &View, key: Key)
+where
+    T: Editable + Undoable,
+{
+    let line = content.line();
+    match key {
+        Key::Char('a') if char_under(content) != '\n' => content.step(Movement::Right),
+        Key::Char('I') => first_non_blank(content, line),
+        Key::Char('A') => content.step(Movement::LineEnd),
+        Key::Char('o') => {
+            content.step(Movement::LineEnd);
+            if view.settings().auto_indent {
+                new_line(content);
+            } else {
+                content.insert('\n');
+            }
+        }
+        Key::Char('O') => {
+            content.move_at(line, 0);
+            content.insert('\n');
+            content.step(Movement::Left);
+        }
+        _ => {}
+    }
+}
+
+// Puts the text of the clipboard after the cursor, or before it, whole lines going below or
+// above the current one
+fn put<T>(content: &mut T, ctx: &mut Context, before: bool, count: usize)
+where
+    T: Editable + Selectable + Undoable,
+{
+    let text = ctx.clipboard.paste().repeat(count);
+    if text.is_empty() {
+        return;
+    }
+    content.reset_sel();
+    content.reset_cursors();
+    content.begin_group();
+    if text.ends_with('\n') {
+        let line = content.line();
+        let target = if before { line } else { line + 1 };
+        if target < content.line_count() {
+            content.move_at(target, 0);
+            text.chars().for_each(|c| content.insert(c));
+        } else {
+            // below the last line, whose line break ends the text
+            content.move_to(content.len() - 1);
+            content.insert('\n');
+            text[..text.len() - 1]
+                .chars()
+                .for_each(|c| content.insert(c));
+        }
+        first_non_blank(content, target);
+    } else {
+        if !before && char_under(content) != '\n' {
+            content.step(Movement::Right);
+        }
+        text.chars().for_each(|c| content.insert(c));
+        content.step(Movement::Left);
+    }
+    content.end_group();
+}
+
+// Replays the keys of the last change, a count replacing the ones it was made with
+fn repeat<T>(content: &mut T, view: &mut View, ctx: &mut Context, count: Option<usize>) -> State
+where
+    T: Editable + Saveable + Undoable + Selectable + Modifiable,
+{
+    let last_change = match ctx.vi {
+        Some(ref vi) if !vi.last_change.is_empty() => vi.last_change.clone(),
+        _ => return State::Normal(Pending::default()),
+    };
+    let keys = match count {
+        Some(n) => {
+            let mut keys: Vec<Key> = n.to_string().chars().map(Key::Char).collect();
+            keys.extend(without_counts(&last_change));
+            keys
+        }
+        None => last_change,
+    };
+    let mut state = State::Normal(Pending::default());
+    for key in keys {
+        state = state.handle(content, view, ctx, Event::Key(key));
+    }
+    state
+}
+
+// Keys of a change without the counts typed before its operator and its motion
+fn without_counts(keys: &[Key]) -> Vec<Key> {
+    // a count can't start with 0, which is a motion
+    let count_len = |keys: &[Key]| match keys.first() {
+        Some(Key::Char('1'..='9')) => keys
+            .iter()
+            .take_while(|key| matches!(key, Key::Char(c) if c.is_ascii_digit()))
+            .count(),
+        _ => 0,
+    };
+    let keys = &keys[count_len(keys)..];
+    match keys.split_first() {
+        Some((&op, rest)) if matches!(op, Key::Char('d') | Key::Char('c') | Key::Char('y')) => {
+            let mut stripped = vec![op];
+            stripped.extend_from_slice(&rest[count_len(rest)..]);
+            stripped
+        }
+        _ => keys.to_vec(),
+    }
+}
+
+// Remembers the keys of a change made at once for . to repeat it, none leaving it nothing to
+// repeat
+fn done(ctx: &mut Context, keys: Vec<Key>) {
+    if let Some(ref mut vi) = ctx.vi {
+        vi.last_change = keys;
+    }
+}
+
+// Goes into insert mode, the keys typed in it making the rest of the change
+fn start_insert(ctx: &mut Context, keys: Vec<Key>) -> State {
+    if let Some(ref mut vi) = ctx.vi {
+        vi.inserting = true;
+        vi.change = if keys.is_empty() {
+            vi.last_change.clear();
+            None
+        } else {
+            Some(keys)
+        };
+    }
+    State::ViInsert
+}
+
+// Drops the selection of a match found by a search, leaving the cursor at its start
+pub(super) fn leave_match<T>(content: &mut T)
+where
+    T: Editable + Selectable,
+{
+    if let Some(sel) = *content.sel() {
+        content.move_to(sel.beg());
+    }
+    content.reset_sel();
+}
+
+// Selects from the anchor to the cursor, both included, or the lines between them
+fn select<T>(content: &mut T, visual: &Visual)
+where
+    T: Editable + Selectable,
+{
+    let pos = content.pos();
+    let last = content.len() - 1;
+    let (beg, end) = if visual.lines {
+        let (first, last_line) = visual_lines(content, visual);
+        let beg = content.line_index_to_char_index(first);
+        let end = content.line_index_to_char_index(last_line) + line_len(content, last_line);
+        (beg, cmp::min(end, last))
+    } else {
+        let (beg, end) = (cmp::min(visual.anchor, pos), cmp::max(visual.anchor, pos));
+        (beg, cmp::min(end + 1, last))
+    };
+    let sel = if pos < visual.anchor {
+        Selection::new(end, beg)
+    } else {
+        Selection::new(beg, end)
+    };
+    content.set_sel(sel);
+}
+
+// First and last lines of the visual selection
+fn visual_lines<T: Editable>(content: &mut T, visual: &Visual) -> (usize, usize) {
+    let line = content.line();
+    let pos = content.pos();
+    content.move_to(visual.anchor);
+    let anchor_line = content.line();
+    content.move_to(pos);
+    (cmp::min(line, anchor_line), cmp::max(line, anchor_line))
+}
+
+// Whether the selection lies within a line, its line break left out
+fn within_line<T: Editable>(content: &mut T, sel: Selection) -> bool {
+    let pos = content.pos();
+    content.move_to(sel.beg());
+    let line = content.line();
+    let line_break = content.line_index_to_char_index(line) + line_len(content, line) - 1;
+    content.move_to(pos);
+    sel.end() <= line_break
+}
+
+fn span_text<T: Editable>(content: &T, span: Span) -> String {
+    match span {
+        Span::Chars(beg, end) => content.iter().skip(beg).take(end - beg).collect(),
+        Span::Lines(first, last) => (first..=last)
+            .flat_map(|line| content.iter_line(line))
+            .collect(),
+    }
+}
+
+// Removes the span, whole lines going along with their line break
+fn remove_span<T: Editable>(content: &mut T, span: Span) {
+    let (beg, end) = match span {
+        Span::Chars(beg, end) => (beg, end),
+        Span::Lines(first, last) => {
+            let beg = content.line_index_to_char_index(first);
+            if last + 1 < content.line_count() {
+                (beg, content.line_index_to_char_index(last + 1))
+            } else {
+                // the line break ending the text stays, that of the line before going instead
+                (beg.saturating_sub(1), content.len() - 1)
+            }
+        }
+    };
+    remove(content, beg, end);
+}
+
+fn remove<T: Editable>(content: &mut T, beg: usize, end: usize) {
+    content.move_to(end);
+    for _ in beg..end {
+        content.delete();
+    }
+}
+
+// Keeps the cursor off the line break, as there is nothing to do there in normal mode
+fn clamp<T: Editable>(content: &mut T) {
+    if content.col() > 0 && char_under(content) == '\n' {
+        content.step(Movement::Left);
+    }
+}
+
+fn first_non_blank<T: Editable>(content: &mut T, line: usize) {
+    let indent = content
+        .iter_line(line)
+        .take_while(|&c| c == ' ' || c == '\t')
+        .count();
+    content.move_at(line, indent);
+}
+
+// Chars of a line, its line break included
+fn line_len<T: Editable>(content: &T, line: usize) -> usize {
+    content.iter_line(line).count()
+}
+
+fn char_under<T: Editable>(content: &T) -> char {
+    content
+        .iter_line(content.line())
+        .nth(content.col())
+        .unwrap_or('\n')
+}
+
+fn char_at<T: Editable>(content: &mut T, pos: usize) -> char {
+    let cursor = content.pos();
+    content.move_to(pos);
+    let c = char_under(content);
+    content.move_to(cursor);
+    c
+}
+
+fn is_blank(c: char) -> bool {
+    c.is_whitespace()
+}
+
+// Words are made of word chars or of other non blank chars, big ones of any non blank chars
+fn class(c: char, big: bool) -> u8 {
+    if is_blank(c) {
+        0
+    } else if big || is_word_char(c) {
+        1
+    } else {
+        2
+    }
+}
+
+// Positions and chars from the cursor on
+fn chars_after<T: Editable>(content: &T) -> impl Iterator<Item = (usize, char)> + '_ {
+    let col = content.col();
+    (content.line()..content.line_count())
+        .flat_map(move |line| {
+            let start = content.line_index_to_char_index(line);
+            content
+                .iter_line(line)
+                .enumerate()
+                .map(move |(i, c)| (start + i, c))
+        })
+        .skip(col)
+}
+
+// Positions and chars before the cursor, going backward
+fn chars_before<T: Editable>(content: &T) -> impl Iterator<Item = (usize, char)> + '_ {
+    let (line, col) = (content.line(), content.col());
+    (0..=line).rev().flat_map(move |l| {
+        let start = content.line_index_to_char_index(l);
+        let chars: Vec<char> = content.iter_line(l).collect();
+        let end = if l == line { col } else { chars.len() };
+        chars
+            .into_iter()
+            .take(end)
+            .enumerate()
+            .rev()
+            .map(move |(i, c)| (start + i, c))
+    })
+}
+
+fn next_word_start<T: Editable>(content: &T, big: bool) -> usize {
+    let mut chars = chars_after(content);
+    let (mut last, mut prev) = match chars.next() {
+        Some(first) => first,
+        None => return content.pos(),
+    };
+    for (pos, c) in chars {
+        // an empty line counts as a word
+        let empty_line = c == '\n' && prev == '\n';
+        if empty_line || (!is_blank(c) && class(c, big) != class(prev, big)) {
+            return pos;
+        }
+        prev = c;
+        last = pos;
+    }
+    last
+}
+
+fn next_word_end<T: Editable>(content: &T, big: bool) -> usize {
+    let mut chars = chars_after(content)
+        .skip(1)
+        .skip_while(|&(_, c)| is_blank(c));
+    let (mut end, c) = match chars.next() {
+        Some(first) => first,
+        None => return content.len() - 1,
+    };
+    let word = class(c, big);
+    for (pos, c) in chars {
+        if class(c, big) != word {
+            break;
+        }
+        end = pos;
+    }
+    end
+}
+
+fn prev_word_start<T: Editable>(content: &T, big: bool) -> usize {
+    let mut chars = chars_before(content).skip_while(|&(_, c)| is_blank(c));
+    let (mut beg, c) = match chars.next() {
+        Some(first) => first,
+        None => return 0,
+    };
+    let word = class(c, big);
+    for (pos, c) in chars {
+        if class(c, big) != word {
+            break;
+        }
+        beg = pos;
+    }
+    beg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::clipboard::Clipboard;
+    use super::*;
+    use crate::config::Settings;
+    use crate::view::Screen;
+    use std::rc::Rc;
+    use syntect::highlighting::Theme;
+    use syntect::parsing::SyntaxSetBuilder;
+
+    // Text with the cursor at its start
+    fn text(s: &str) -> Text {
+        let mut text = Text::empty();
+        for c in s.chars() {
+            text.insert(c);
+        }
+        text.move_to(0);
+        text
+    }
+
+    // Types the keys in normal mode, Esc being written \x1b, giving the text and where the cursor
+    // ends up
+    fn type_keys(s: &str, keys: &str) -> (String, usize) {
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_plain_text_syntax();
+        let syntax_set = builder.build();
+        let theme = Theme::default();
+        let mut view = View::new(
+            &theme,
+            syntax_set.find_syntax_plain_text(),
+            &syntax_set,
+            Settings::default(),
+            Rc::new(Screen::headless(80, 24)),
+        );
+        let mut ctx = Context::new();
+        ctx.clipboard = Clipboard::internal();
+        ctx.vi = Some(Vi::default());
+        let mut content = Select::new(Recorded::new(text(s)));
+        let mut state = State::Insert.in_mode(&mut content, &ctx);
+        for c in keys.chars() {
+            let key = if c == '\x1b' { Key::Esc } else { Key::Char(c) };
+            state = state.handle(&mut content, &mut view, &mut ctx, Event::Key(key));
+        }
+        (content.iter().collect(), content.pos())
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut content = text("foo.bar  baz\nqux");
+        move_by(&mut content, Motion::WordStart(false), None, false);
+        assert_eq!(content.pos(), 3);
+        move_by(&mut content, Motion::WordStart(false), Some(2), false);
+        assert_eq!(content.pos(), 9);
+        move_by(&mut content, Motion::WordBack(true), None, false);
+        assert_eq!(content.pos(), 0);
+        move_by(&mut content, Motion::WordStart(true), None, false);
+        assert_eq!(content.pos(), 9);
+        move_by(&mut content, Motion::WordEnd(false), None, false);
+        assert_eq!(content.pos(), 11);
+        move_by(&mut content, Motion::WordStart(false), None, false);
+        assert_eq!(content.pos(), 13);
+    }
+
+    #[test]
+    fn line_motions() {
+        let mut content = text("  foo\nbar\nbaz");
+        assert!(matches!(
+            move_by(&mut content, Motion::LineEnd, None, false),
+            Some(Kind::Inclusive)
+        ));
+        assert_eq!(content.pos(), 5);
+        move_by(&mut content, Motion::FirstNonBlank, None, false);
+        assert_eq!(content.pos(), 2);
+        move_by(&mut content, Motion::LineStart, None, false);
+        assert_eq!(content.pos(), 0);
+        assert!(matches!(
+            move_by(&mut content, Motion::Down, Some(2), false),
+            Some(Kind::Linewise)
+        ));
+        assert_eq!(content.line(), 2);
+        // there is no line to go down to
+        assert!(move_by(&mut content, Motion::Down, None, false).is_none());
+        move_by(&mut content, Motion::FirstLine, Some(2), false);
+        assert_eq!(content.line(), 1);
+    }
+
+    #[test]
+    fn right_stops_before_line_break_unless_operating() {
+        let mut content = text("ab\ncd");
+        move_by(&mut content, Motion::Right, Some(5), false);
+        assert_eq!(content.pos(), 1);
+        content.move_to(0);
+        move_by(&mut content, Motion::Right, Some(5), true);
+        assert_eq!(content.pos(), 2);
+    }
+
+    #[test]
+    fn operators_and_motions() {
+        assert_eq!(type_keys("foo bar baz", "dw").0, "bar baz\n");
+        assert_eq!(type_keys("foo bar baz", "d2w").0, "baz\n");
+        assert_eq!(type_keys("foo bar baz", "2dw").0, "baz\n");
+        assert_eq!(type_keys("foo bar baz", "de").0, " bar baz\n");
+        assert_eq!(type_keys("foo bar baz", "wD").0, "foo \n");
+        assert_eq!(type_keys("foo bar baz", "3x").0, " bar baz\n");
+        // cw leaves the blanks after the word
+        assert_eq!(type_keys("foo bar", "cwqux\x1b").0, "qux bar\n");
+        assert_eq!(type_keys("a\nb\nc", "jdd"), ("a\nc\n".to_string(), 2));
+        assert_eq!(type_keys("a\nb\nc", "2dd").0, "c\n");
+        assert_eq!(type_keys("a\nb", "yyjp").0, "a\nb\na\n");
+    }
+
+    #[test]
+    fn repeats_changes() {
+        assert_eq!(type_keys("a b c d", "dw.").0, "c d\n");
+        assert_eq!(type_keys("foo bar baz", "cwx\x1bw.").0, "x x baz\n");
+        assert_eq!(type_keys("a\nb\nc\nd", "dd.").0, "c\nd\n");
+    }
+
+    #[test]
+    fn count_of_repeat_replaces_every_count() {
+        let words = "a b c d e f g h";
+        assert_eq!(type_keys(words, "d3w.").0, "g h\n");
+        assert_eq!(type_keys(words, "d3w2.").0, "f g h\n");
+        assert_eq!(type_keys(words, "2d2w3.").0, "h\n");
+        assert_eq!(type_keys(words, "3x2.").0, " d e f g h\n");
+    }
+
+    #[test]
+    fn repeats_visual_changes() {
+        assert_eq!(type_keys("abcdefg", "vld.").0, "efg\n");
+        assert_eq!(type_keys("abcdefg", "vlcx\x1bl.").0, "xxefg\n");
+        assert_eq!(type_keys("a\nb\nc\nd\ne", "Vjd.").0, "e\n");
+        // a selection going over a line break leaves nothing to repeat
+        assert_eq!(type_keys("abc\ndef\nghi", "xvjd.").0, "ef\nghi\n");
+    }
+
+    #[test]
+    fn strips_counts() {
+        let keys = |s: &str| s.chars().map(Key::Char).collect::<Vec<_>>();
+        assert_eq!(without_counts(&keys("12d3w")), keys("dw"));
+        assert_eq!(without_counts(&keys("d0")), keys("d0"));
+        assert_eq!(without_counts(&keys("10x")), keys("x"));
+        assert_eq!(without_counts(&keys("cw10")), keys("cw10"));
+    }
+
+    #[test]
+    fn leaving_insert_mode() {
+        let mut vi = Vi {
+            inserting: true,
+            change: Some(vec![Key::Char('i')]),
+            last_change: vec![Key::Char('x')],
+        };
+        vi.leave_insert();
+        assert!(!vi.inserting);
+        assert!(vi.change.is_none());
+        assert_eq!(vi.last_change, vec![Key::Char('x')]);
+    }
+}
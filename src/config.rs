@@ -0,0 +1,357 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use syntect::dumps::from_binary;
+use syntect::highlighting::{Theme, ThemeSet};
+
+const CONFIG_FILE: &str = "config.toml";
+// keys of the settings, which each language can also have
+const SETTINGS: &[&str] = &[
+    "tab_width",
+    "soft_tabs",
+    "scroll_speed",
+    "line_numbers",
+    "auto_indent",
+];
+// keys found only at the top of the file
+const TOP_LEVEL: &[&str] = &[
+    "theme",
+    "status",
+    "languages",
+    "keys",
+    "prompt_keys",
+    "vi_mode",
+];
+// fields of the status line layout
+const STATUS: &[&str] = &["left", "right"];
+
+/// User settings read from the config file, any of them being optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // name of one of the themes coming with syntect or path to a .tmTheme file, gruvbox otherwise
+    theme: Option<String>,
+    #[serde(flatten)]
+    settings: Settings,
+    // settings of a language, by the name of its syntax (e.g. "Rust" or "Python")
+    languages: HashMap<String, Overrides>,
+    // commands bound to keys, e.g. "ctrl-k ctrl-u" = "undo"
+    keys: HashMap<String, String>,
+    // commands of the prompts bound to single keys, e.g. "ctrl-g" = "cancel"
+    prompt_keys: HashMap<String, String>,
+    // modal editing with the keys of vi
+    vi_mode: bool,
+    // keys which aren't settings, most likely misspelled
+    #[serde(skip)]
+    unknown_keys: Vec<String>,
+}
+
+/// How a buffer is edited and shown
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub tab_width: usize,
+    // indent with spaces rather than tabs
+    pub soft_tabs: bool,
+    // lines scrolled by the mouse wheel
+    pub scroll_speed: usize,
+    pub line_numbers: bool,
+    // start new lines with the indentation of the previous one
+    pub auto_indent: bool,
+    pub status: StatusLayout,
+}
+
+/// What the status line shows on each side, fields such as {name} being replaced by their value
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StatusLayout {
+    pub left: String,
+    pub right: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tab_width: 4,
+            soft_tabs: false,
+            scroll_speed: 2,
+            line_numbers: true,
+            auto_indent: false,
+            status: StatusLayout::default(),
+        }
+    }
+}
+
+impl Default for StatusLayout {
+    fn default() -> Self {
+        StatusLayout {
+            left: "{mode}  {name} {modified}".to_string(),
+            right: "{matches}  {percent}% {line}/{lines}: {column}".to_string(),
+        }
+    }
+}
+
+impl StatusLayout {
+    // Replaces the fields of a side by their value, those left empty leaving no space at its ends
+    pub fn fill(side: &str, fields: &[(&str, String)]) -> String {
+        let mut filled = side.to_string();
+        for (name, value) in fields {
+            filled = filled.replace(&format!("{{{}}}", name), value);
+        }
+        filled.trim().to_string()
+    }
+}
+
+/// Settings replacing the general ones, those of a language or those set for a buffer
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Overrides {
+    pub tab_width: Option<usize>,
+    pub soft_tabs: Option<bool>,
+    pub scroll_speed: Option<usize>,
+    pub line_numbers: Option<bool>,
+    pub auto_indent: Option<bool>,
+}
+
+impl Overrides {
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.tab_width = self
+            .tab_width
+            .filter(|&w| w > 0)
+            .unwrap_or(settings.tab_width);
+        settings.soft_tabs = self.soft_tabs.unwrap_or(settings.soft_tabs);
+        settings.scroll_speed = self.scroll_speed.unwrap_or(settings.scroll_speed);
+        settings.line_numbers = self.line_numbers.unwrap_or(settings.line_numbers);
+        settings.auto_indent = self.auto_indent.unwrap_or(settings.auto_indent);
+    }
+
+    // Takes the settings the others override, keeping its own otherwise
+    pub fn merge(&mut self, others: Overrides) {
+        self.tab_width = others.tab_width.or(self.tab_width);
+        self.soft_tabs = others.soft_tabs.or(self.soft_tabs);
+        self.scroll_speed = others.scroll_speed.or(self.scroll_speed);
+        self.line_numbers = others.line_numbers.or(self.line_numbers);
+        self.auto_indent = others.auto_indent.or(self.auto_indent);
+    }
+}
+
+impl Config {
+    // Reads the config file if there is one, telling what is wrong with it otherwise
+    pub fn load() -> Result<Self, String> {
+        let path = match config_dir() {
+            Some(dir) => dir.join(CONFIG_FILE),
+            None => return Ok(Config::default()),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        if config.settings.tab_width == 0 {
+            return Err("tab_width can't be 0".to_string());
+        }
+        let value: toml::Value = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+        config.unknown_keys = unknown_keys(&value);
+        Ok(config)
+    }
+
+    // Settings for a syntax, along with those of its language
+    pub fn settings(&self, syntax: &str) -> Settings {
+        let mut settings = self.settings.clone();
+        let overrides = self
+            .languages
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(syntax))
+            .map(|(_, overrides)| overrides);
+        if let Some(overrides) = overrides {
+            overrides.apply(&mut settings);
+        }
+        settings
+    }
+
+    pub fn keys(&self) -> &HashMap<String, String> {
+        &self.keys
+    }
+
+    pub fn prompt_keys(&self) -> &HashMap<String, String> {
+        &self.prompt_keys
+    }
+
+    pub fn vi_mode(&self) -> bool {
+        self.vi_mode
+    }
+
+    pub fn unknown_keys(&self) -> &[String] {
+        &self.unknown_keys
+    }
+
+    // The theme asked for, or gruvbox along with why it couldn't be loaded
+    pub fn theme(&self) -> (Theme, Option<String>) {
+        let gruvbox = || from_binary(include_bytes!("../assets/gruvbox.themedump"));
+        let name = match self.theme {
+            Some(ref name) if name != "gruvbox" => name,
+            _ => return (gruvbox(), None),
+        };
+        if name.ends_with(".tmTheme") {
+            return match ThemeSet::get_theme(name) {
+                Ok(theme) => (theme, None),
+                Err(e) => (gruvbox(), Some(format!("Can't load theme {}: {}", name, e))),
+            };
+        }
+        match ThemeSet::load_defaults().themes.remove(name) {
+            Some(theme) => (theme, None),
+            None => (gruvbox(), Some(format!("Unknown theme {}", name))),
+        }
+    }
+}
+
+// Directory the config file is in, following the XDG base directories
+fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("smith"))
+}
+
+// Keys of the file which don't match any setting, the ones of a language being prefixed with it
+fn unknown_keys(value: &toml::Value) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for (key, value) in value.as_table().into_iter().flatten() {
+        if key == "languages" {
+            for (language, overrides) in value.as_table().into_iter().flatten() {
+                for (key, _) in overrides.as_table().into_iter().flatten() {
+                    if !SETTINGS.contains(&key.as_str()) {
+                        unknown.push(format!("languages.{}.{}", language, key));
+                    }
+                }
+            }
+        } else if key == "status" {
+            for (key, _) in value.as_table().into_iter().flatten() {
+                if !STATUS.contains(&key.as_str()) {
+                    unknown.push(format!("status.{}", key));
+                }
+            }
+        } else if !SETTINGS.contains(&key.as_str()) && !TOP_LEVEL.contains(&key.as_str()) {
+            unknown.push(key.clone());
+        }
+    }
+    unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_without_settings() {
+        let config = Config::parse("").unwrap();
+        let settings = config.settings("Rust");
+        assert_eq!(settings.tab_width, 4);
+        assert!(!settings.soft_tabs);
+        assert!(settings.line_numbers);
+        assert!(!config.vi_mode());
+        assert!(config.unknown_keys().is_empty());
+    }
+
+    #[test]
+    fn languages_override_settings() {
+        let config = Config::parse(
+            "tab_width = 8\nsoft_tabs = false\n\n[languages.python]\nsoft_tabs = true\ntab_width = 0\n\n[languages.Rust]\ntab_width = 2\n",
+        )
+        .unwrap();
+
+        let python = config.settings("Python");
+        assert!(python.soft_tabs);
+        // a width of 0 is ignored
+        assert_eq!(python.tab_width, 8);
+        assert_eq!(config.settings("Rust").tab_width, 2);
+        let plain = config.settings("Plain Text");
+        assert_eq!(plain.tab_width, 8);
+        assert!(!plain.soft_tabs);
+    }
+
+    #[test]
+    fn zero_tab_width() {
+        assert_eq!(
+            Config::parse("tab_width = 0").unwrap_err(),
+            "tab_width can't be 0"
+        );
+    }
+
+    #[test]
+    fn toml_errors() {
+        let e = Config::parse("theme = \"gruvbox\"\ntab_width = \n").unwrap_err();
+        assert!(e.contains("line 2"), "{}", e);
+        let e = Config::parse("tab_width = \"four\"").unwrap_err();
+        assert!(e.contains("expected usize"), "{}", e);
+    }
+
+    #[test]
+    fn unknown_keys_reported() {
+        let config = Config::parse(
+            "tabwidth = 2\nvi_mode = true\n\n[languages.python]\nsofttabs = true\n\n[keys]\n\"ctrl-k\" = \"undo\"\n\n[prompt_keys]\n\"ctrl-g\" = \"cancel\"\n",
+        )
+        .unwrap();
+        assert!(config.vi_mode());
+        assert_eq!(
+            config.unknown_keys(),
+            ["languages.python.softtabs", "tabwidth"]
+        );
+    }
+
+    #[test]
+    fn status_layout() {
+        let config =
+            Config::parse("[status]\nright = \"{line}:{column}\"\nmiddle = \"\"\n").unwrap();
+        let status = config.settings("Rust").status;
+        assert_eq!(status.left, StatusLayout::default().left);
+        assert_eq!(status.right, "{line}:{column}");
+        assert_eq!(config.unknown_keys(), ["status.middle"]);
+    }
+
+    #[test]
+    fn fill_status() {
+        let fields = [
+            ("mode", String::new()),
+            ("name", "main.rs".to_string()),
+            ("modified", "*".to_string()),
+        ];
+        assert_eq!(
+            StatusLayout::fill("{mode}  {name} {modified}", &fields),
+            "main.rs *"
+        );
+        assert_eq!(
+            StatusLayout::fill("[{name}] {other}", &fields),
+            "[main.rs] {other}"
+        );
+    }
+
+    #[test]
+    fn merge_overrides() {
+        let mut overrides = Overrides {
+            tab_width: Some(2),
+            soft_tabs: Some(true),
+            ..Overrides::default()
+        };
+        overrides.merge(Overrides {
+            tab_width: Some(8),
+            line_numbers: Some(false),
+            ..Overrides::default()
+        });
+        let mut settings = Settings::default();
+        overrides.apply(&mut settings);
+        assert_eq!(settings.tab_width, 8);
+        assert!(settings.soft_tabs);
+        assert!(!settings.line_numbers);
+        assert!(!settings.auto_indent);
+    }
+}
@@ -1,12 +1,15 @@
 mod record;
+mod search;
 mod select;
 mod text;
 
 pub use self::record::Recorded;
 pub use self::record::Undoable;
-pub use self::select::{Select, Selectable};
+pub use self::search::Search;
+pub use self::select::{Select, Selectable, Selection};
 pub use self::text::Text;
 
+use std::fs;
 use std::io::Result;
 
 pub trait Editable {
@@ -22,9 +25,9 @@ pub trait Editable {
     fn col(&self) -> usize;
     fn line_count(&self) -> usize;
     fn len(&self) -> usize;
-    fn iter(&self) -> CharIter;
-    fn lines(&self) -> LineIter;
-    fn iter_line(&self, line: usize) -> CharIter;
+    fn iter(&self) -> CharIter<'_>;
+    fn lines(&self) -> LineIter<'_>;
+    fn iter_line(&self, line: usize) -> CharIter<'_>;
     fn line_index_to_char_index(&self, line: usize) -> usize;
 }
 
@@ -54,4 +57,75 @@ pub enum Movement {
     LineEnd,
     PageUp(usize),
     PageDown(usize),
+    WordLeft,
+    WordRight,
+}
+
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Word touching the cursor, if any
+pub fn word_under_cursor<T>(content: &T) -> Option<Selection>
+where
+    T: Editable,
+{
+    let chars: Vec<char> = content.iter_line(content.line()).collect();
+    let mut beg = content.col();
+    while beg > 0 && is_word_char(chars[beg - 1]) {
+        beg -= 1;
+    }
+    let mut end = content.col();
+    while end < chars.len() && is_word_char(chars[end]) {
+        end += 1;
+    }
+    if beg == end {
+        return None;
+    }
+    let line_start = content.line_index_to_char_index(content.line());
+    Some(Selection::new(line_start + beg, line_start + end))
+}
+
+// Name of the file, or a placeholder for content which doesn't have one yet
+pub fn display_name<T: Named>(text: &T) -> String {
+    if text.name().is_empty() {
+        "[No Name]".to_string()
+    } else {
+        text.name().clone()
+    }
+}
+
+// Whether both names lead to the same file, however they are spelled
+pub fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn same_file_however_spelled() {
+        let dir = env::temp_dir().join(format!("smith-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("foo.rs");
+        fs::write(&file, "").unwrap();
+        let name = file.to_str().unwrap();
+        let dotted = dir.join(".").join("foo.rs");
+        let other = dir.join("bar.rs");
+
+        assert!(same_file(name, name));
+        assert!(same_file(name, dotted.to_str().unwrap()));
+        assert!(!same_file(name, other.to_str().unwrap()));
+        assert!(!same_file(
+            other.to_str().unwrap(),
+            dir.join("baz.rs").to_str().unwrap()
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
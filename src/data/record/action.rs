@@ -8,6 +8,7 @@ pub enum Action {
     Delete(String),
     Move(isize),
     DeleteForward(String),
+    Group(Vec<Action>),
 }
 
 impl Action {
@@ -37,6 +38,11 @@ impl Action {
                     content.insert_forward(c);
                 }
             }
+            Action::Group(ref acts) => {
+                for a in acts {
+                    a.apply(content);
+                }
+            }
         };
     }
 
@@ -47,6 +53,9 @@ impl Action {
             Action::Move(ref rel) => Action::Move(-rel),
             Action::InsertForward(ref s) => Action::DeleteForward(s.clone()),
             Action::DeleteForward(ref s) => Action::InsertForward(s.clone()),
+            Action::Group(ref acts) => {
+                Action::Group(acts.iter().rev().map(Action::invert).collect())
+            }
         }
     }
 
@@ -90,10 +99,26 @@ impl Action {
                 };
                 *rel += act_rel;
             }
+            Action::Group(ref mut acts) => {
+                let act_acts = match act {
+                    Action::Group(a) => a,
+                    _ => panic!("Trying to join dissimilar Actions"),
+                };
+                acts.extend(act_acts);
+            }
         }
     }
 
     pub fn same_variant(&self, other: &Action) -> bool {
         mem::discriminant(self) == mem::discriminant(other)
     }
+
+    // Whether this action only moves the cursor around
+    pub fn is_move(&self) -> bool {
+        match *self {
+            Action::Move(_) => true,
+            Action::Group(ref acts) => acts.iter().all(Action::is_move),
+            _ => false,
+        }
+    }
 }
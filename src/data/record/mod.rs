@@ -7,7 +7,6 @@ use super::{CharIter, Editable, LineIter, Modifiable, Movement, Named, Saveable}
 use delegate_attr::delegate;
 use std::collections::VecDeque;
 use std::io::Result;
-use std::usize;
 
 const HISTORY_SIZE: usize = usize::MAX;
 const UNDO_SIZE: usize = usize::MAX;
@@ -15,7 +14,9 @@ const UNDO_SIZE: usize = usize::MAX;
 pub trait Undoable {
     fn undo(&mut self);
     fn redo(&mut self);
-    fn history_len(&self) -> usize;
+    // Every edit made until the matching end_group is undone as a single step
+    fn begin_group(&mut self);
+    fn end_group(&mut self);
 }
 
 pub struct Recorded<T>
@@ -25,6 +26,8 @@ where
     content: T,
     history: VecDeque<Action>,
     undone: VecDeque<Action>,
+    group: Vec<Action>,
+    group_depth: usize,
 }
 
 impl<T> Recorded<T>
@@ -36,12 +39,26 @@ where
             content,
             history: VecDeque::new(),
             undone: VecDeque::new(),
+            group: Vec::new(),
+            group_depth: 0,
         }
     }
     fn record(&mut self, act: Action) {
+        if self.group_depth > 0 {
+            match self.group.last_mut() {
+                Some(a) if a.same_variant(&act) => a.join(act),
+                _ => self.group.push(act),
+            }
+            return;
+        }
         self.undone.clear(); // we are branching to a new sequence of events
         if let Some(a) = self.history.front_mut() {
-            if a.same_variant(&act) {
+            // groups are kept apart unless they are only moving the cursors around
+            let joinable = match act {
+                Action::Group(_) => a.is_move() && act.is_move(),
+                _ => true,
+            };
+            if joinable && a.same_variant(&act) {
                 // join similar actions together
                 a.join(act);
                 return;
@@ -79,8 +96,16 @@ where
         to_redo.apply(&mut self.content);
         self.history.push_front(to_redo);
     }
-    fn history_len(&self) -> usize {
-        self.history.len()
+    fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+    fn end_group(&mut self) {
+        assert!(self.group_depth > 0);
+        self.group_depth -= 1;
+        if self.group_depth == 0 && !self.group.is_empty() {
+            let acts = self.group.drain(..).collect();
+            self.record(Action::Group(acts));
+        }
     }
 }
 
@@ -154,11 +179,11 @@ where
     #[delegate(self.content)]
     fn len(&self) -> usize;
     #[delegate(self.content)]
-    fn iter(&self) -> CharIter;
+    fn iter(&self) -> CharIter<'_>;
     #[delegate(self.content)]
-    fn lines(&self) -> LineIter;
+    fn lines(&self) -> LineIter<'_>;
     #[delegate(self.content)]
-    fn iter_line(&self, line: usize) -> CharIter;
+    fn iter_line(&self, line: usize) -> CharIter<'_>;
     #[delegate(self.content)]
     fn line_index_to_char_index(&self, line: usize) -> usize;
 }
@@ -0,0 +1,164 @@
+use super::{is_word_char, word_under_cursor, Editable, Selectable, Selection};
+
+/// What is being looked for in the text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Search {
+    pub query: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+impl Search {
+    // Search for the other occurrences of the selection, or of the word under the cursor,
+    // along with the occurrence the search starts from
+    pub fn under_cursor<T>(content: &T) -> Option<(Search, Selection)>
+    where
+        T: Editable + Selectable,
+    {
+        let (sel, whole_word) = match *content.sel() {
+            Some(sel) if !sel.is_empty() => (sel, false),
+            Some(_) => return None,
+            None => (word_under_cursor(content)?, true),
+        };
+        let query: String = content
+            .iter()
+            .skip(sel.beg())
+            .take(sel.end() - sel.beg())
+            .collect();
+        if query.contains('\n') || query.trim().is_empty() {
+            return None;
+        }
+        let search = Search {
+            query,
+            case_sensitive: true,
+            whole_word,
+        };
+        Some((search, sel))
+    }
+
+    fn normalize(&self, c: char) -> char {
+        if self.case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    }
+
+    // Ranges of chars matching the query in text, in order and without overlap
+    pub fn find_in(&self, text: &[char]) -> Vec<(usize, usize)> {
+        let query: Vec<char> = self.query.chars().map(|c| self.normalize(c)).collect();
+        let mut matches = Vec::new();
+        if query.is_empty() {
+            return matches;
+        }
+        let mut i = 0;
+        while i + query.len() <= text.len() {
+            let end = i + query.len();
+            let found = text[i..end]
+                .iter()
+                .zip(query.iter())
+                .all(|(&c, &q)| self.normalize(c) == q);
+            let bounded = !self.whole_word
+                || ((i == 0 || !is_word_char(text[i - 1]))
+                    && (end == text.len() || !is_word_char(text[end])));
+            if found && bounded {
+                matches.push((i, end));
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+        matches
+    }
+
+    pub fn find_all<T: Editable>(&self, content: &T) -> Vec<(usize, usize)> {
+        let text: Vec<char> = content.iter().collect();
+        self.find_in(&text)
+    }
+
+    // Index of the first match starting at or after pos, or the last one starting before it when
+    // going backward, wrapping around the text
+    pub fn next_match(matches: &[(usize, usize)], pos: usize, forward: bool) -> Option<usize> {
+        if matches.is_empty() {
+            return None;
+        }
+        if forward {
+            Some(matches.iter().position(|m| m.0 >= pos).unwrap_or(0))
+        } else {
+            Some(
+                matches
+                    .iter()
+                    .rposition(|m| m.0 < pos)
+                    .unwrap_or(matches.len() - 1),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str, case_sensitive: bool, whole_word: bool) -> Search {
+        Search {
+            query: query.to_string(),
+            case_sensitive,
+            whole_word,
+        }
+    }
+
+    fn find(search: &Search, text: &str) -> Vec<(usize, usize)> {
+        let chars: Vec<char> = text.chars().collect();
+        search.find_in(&chars)
+    }
+
+    #[test]
+    fn finds_matches_in_order() {
+        assert_eq!(
+            find(&search("ab", true, false), "ab xab ab"),
+            vec![(0, 2), (4, 6), (7, 9)]
+        );
+        assert!(find(&search("", true, false), "abc").is_empty());
+        assert!(find(&search("abcd", true, false), "abc").is_empty());
+    }
+
+    #[test]
+    fn matches_dont_overlap() {
+        assert_eq!(
+            find(&search("aa", true, false), "aaaaa"),
+            vec![(0, 2), (2, 4)]
+        );
+    }
+
+    #[test]
+    fn case_sensitivity() {
+        assert_eq!(find(&search("Ab", true, false), "ab AB Ab"), vec![(6, 8)]);
+        assert_eq!(
+            find(&search("Ab", false, false), "ab AB Ab"),
+            vec![(0, 2), (3, 5), (6, 8)]
+        );
+        assert_eq!(find(&search("é", false, false), "É"), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn whole_words() {
+        assert_eq!(
+            find(&search("ab", true, true), "ab abc cab ab_ (ab)"),
+            vec![(0, 2), (16, 18)]
+        );
+    }
+
+    #[test]
+    fn next_match_wraps_around() {
+        let matches = [(2, 4), (6, 8), (10, 12)];
+        assert_eq!(Search::next_match(&matches, 0, true), Some(0));
+        assert_eq!(Search::next_match(&matches, 6, true), Some(1));
+        assert_eq!(Search::next_match(&matches, 7, true), Some(2));
+        assert_eq!(Search::next_match(&matches, 11, true), Some(0));
+        assert_eq!(Search::next_match(&matches, 6, false), Some(0));
+        assert_eq!(Search::next_match(&matches, 7, false), Some(1));
+        assert_eq!(Search::next_match(&matches, 2, false), Some(2));
+        assert_eq!(Search::next_match(&[], 0, true), None);
+        assert_eq!(Search::next_match(&[], 0, false), None);
+    }
+}
@@ -1,19 +1,116 @@
 use super::{CharIter, Editable, LineIter, Modifiable, Movement, Named, Saveable, Undoable};
 use delegate_attr::delegate;
+use std::cmp;
 use std::io::Result;
 
-pub type Selection = (usize, usize);
+/// A selection spans the characters between a fixed anchor and a moving head, the head being
+/// where the cursor sits. The anchor may be on either side of the head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    pub fn new(anchor: usize, head: usize) -> Selection {
+        Selection { anchor, head }
+    }
+
+    // first selected char
+    pub fn beg(&self) -> usize {
+        cmp::min(self.anchor, self.head)
+    }
+
+    // one past the last selected char
+    pub fn end(&self) -> usize {
+        cmp::max(self.anchor, self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    pub fn contains(&self, pos: usize) -> bool {
+        pos >= self.beg() && pos < self.end()
+    }
+
+    pub fn flipped(&self) -> Selection {
+        Selection::new(self.head, self.anchor)
+    }
+
+    // Keeps the selection on the same text after len chars were inserted at pos
+    fn adjust_for_insert(&mut self, pos: usize, len: usize) {
+        let beg = self.beg();
+        let end = self.end();
+        let new_beg = if pos <= beg { beg + len } else { beg };
+        let new_end = if pos < end { end + len } else { end };
+        self.set_bounds(new_beg, cmp::max(new_beg, new_end));
+    }
+
+    // Keeps the selection on the same text after len chars were removed at pos
+    fn adjust_for_delete(&mut self, pos: usize, len: usize) {
+        let shift = |x: usize| {
+            if x <= pos {
+                x
+            } else if x >= pos + len {
+                x - len
+            } else {
+                pos
+            }
+        };
+        let new_beg = shift(self.beg());
+        let new_end = shift(self.end());
+        self.set_bounds(new_beg, new_end);
+    }
+
+    fn set_bounds(&mut self, beg: usize, end: usize) {
+        if self.anchor <= self.head {
+            self.anchor = beg;
+            self.head = end;
+        } else {
+            self.anchor = end;
+            self.head = beg;
+        }
+    }
+}
 
 pub trait Selectable {
     fn sel(&self) -> &Option<Selection>;
     fn set_sel(&mut self, selection: Selection);
     fn reset_sel(&mut self);
-    fn in_sel(&self, pos: usize) -> bool {
-        match *self.sel() {
-            Some((beg, end)) => pos >= beg && pos <= end,
-            None => false,
+    // cursors other than the main one, each holding its own selection
+    fn cursors(&self) -> &Vec<Selection>;
+    fn add_cursor(&mut self, cursor: Selection);
+    fn reset_cursors(&mut self);
+    // text of every selection, one per line
+    fn sel_text(&self) -> String;
+    fn delete_sel(&mut self);
+    // positions kept on the same text through edits, such as the cursors of other panes
+    fn set_mark(&mut self, id: usize, pos: usize);
+    fn mark(&self, id: usize) -> Option<usize>;
+    fn remove_mark(&mut self, id: usize);
+}
+
+// How an edit changed the text, so that cursors can be kept on the same text
+#[derive(Clone, Copy)]
+enum Change {
+    Insert(usize, usize),
+    Delete(usize, usize),
+}
+
+impl Change {
+    fn shift(self, sel: &mut Selection) {
+        match self {
+            Change::Insert(pos, len) => sel.adjust_for_insert(pos, len),
+            Change::Delete(pos, len) => sel.adjust_for_delete(pos, len),
         }
     }
+
+    fn shift_pos(self, pos: usize) -> usize {
+        let mut sel = Selection::new(pos, pos);
+        self.shift(&mut sel);
+        sel.head
+    }
 }
 
 pub struct Select<T>
@@ -22,6 +119,8 @@ where
 {
     content: T,
     sel: Option<Selection>,
+    others: Vec<Selection>,
+    marks: Vec<(usize, usize)>,
 }
 
 impl<T> Select<T>
@@ -29,13 +128,105 @@ where
     T: Editable,
 {
     pub fn new(content: T) -> Select<T> {
-        Select { content, sel: None }
+        Select {
+            content,
+            sel: None,
+            others: Vec::new(),
+            marks: Vec::new(),
+        }
+    }
+
+    // Runs edit on the content and reports how it changed the text
+    fn apply<F>(&mut self, edit: &mut F) -> Option<Change>
+    where
+        F: FnMut(&mut T),
+    {
+        let pos = self.content.pos();
+        let len = self.content.len();
+        edit(&mut self.content);
+        let new_len = self.content.len();
+        if new_len > len {
+            Some(Change::Insert(pos, new_len - len))
+        } else if new_len < len {
+            Some(Change::Delete(self.content.pos(), len - new_len))
+        } else {
+            None
+        }
+    }
+
+    fn shift_all(&mut self, change: Change, except: Option<usize>) {
+        for (i, other) in self.others.iter_mut().enumerate() {
+            if Some(i) != except {
+                change.shift(other);
+            }
+        }
+        if let Some(ref mut sel) = self.sel {
+            change.shift(sel);
+        }
+        self.shift_marks(change);
+    }
+
+    fn shift_marks(&mut self, change: Change) {
+        for (_, pos) in self.marks.iter_mut() {
+            *pos = change.shift_pos(*pos);
+        }
+    }
+
+    // Drops the cursors that ended up on top of another one
+    fn merge_cursors(&mut self) {
+        let main = self.content.pos();
+        let mut seen = vec![main];
+        self.others.retain(|other| {
+            if seen.contains(&other.head) {
+                false
+            } else {
+                seen.push(other.head);
+                true
+            }
+        });
+    }
+}
+
+impl<T> Select<T>
+where
+    T: Editable + Undoable,
+{
+    // Runs edit at every secondary cursor and then at the main one as a single undoable step,
+    // keeping all the other cursors and selections on the same text
+    fn edit_all<F>(&mut self, mut edit: F)
+    where
+        F: FnMut(&mut T),
+    {
+        let grouped = !self.others.is_empty();
+        if grouped {
+            self.content.begin_group();
+        }
+        let mut main = self.content.pos();
+        for i in 0..self.others.len() {
+            self.content.move_to(self.others[i].head);
+            if let Some(change) = self.apply(&mut edit) {
+                self.shift_all(change, Some(i));
+                main = change.shift_pos(main);
+            }
+            let pos = self.content.pos();
+            self.others[i] = Selection::new(pos, pos);
+        }
+        if grouped {
+            self.content.move_to(main);
+        }
+        if let Some(change) = self.apply(&mut edit) {
+            self.shift_all(change, None);
+        }
+        if grouped {
+            self.content.end_group();
+            self.merge_cursors();
+        }
     }
 }
 
 impl<T> Selectable for Select<T>
 where
-    T: Editable,
+    T: Editable + Undoable,
 {
     fn sel(&self) -> &Option<Selection> {
         &self.sel
@@ -47,29 +238,180 @@ where
 
     fn reset_sel(&mut self) {
         self.sel = None;
+        for other in self.others.iter_mut() {
+            other.anchor = other.head;
+        }
+    }
+
+    fn cursors(&self) -> &Vec<Selection> {
+        &self.others
+    }
+
+    fn add_cursor(&mut self, cursor: Selection) {
+        self.others.push(cursor);
+        self.merge_cursors();
+    }
+
+    fn reset_cursors(&mut self) {
+        self.others.clear();
+    }
+
+    fn sel_text(&self) -> String {
+        let mut sels: Vec<Selection> = self
+            .others
+            .iter()
+            .chain(self.sel.iter())
+            .filter(|sel| !sel.is_empty())
+            .cloned()
+            .collect();
+        sels.sort_by_key(|sel| sel.beg());
+        sels.iter()
+            .map(|sel| {
+                self.content
+                    .iter()
+                    .skip(sel.beg())
+                    .take(sel.end() - sel.beg())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn delete_sel(&mut self) {
+        let grouped = !self.others.is_empty();
+        if grouped {
+            self.content.begin_group();
+        }
+        let mut main = self.content.pos();
+        for i in 0..self.others.len() {
+            let sel = self.others[i];
+            if !sel.is_empty() {
+                self.content.move_to(sel.end());
+                for _ in sel.beg()..sel.end() {
+                    self.content.delete();
+                }
+                let change = Change::Delete(sel.beg(), sel.end() - sel.beg());
+                self.shift_all(change, Some(i));
+                main = change.shift_pos(main);
+            }
+            self.others[i] = Selection::new(sel.beg(), sel.beg());
+        }
+        match self.sel {
+            Some(sel) if !sel.is_empty() => {
+                self.content.move_to(sel.end());
+                for _ in sel.beg()..sel.end() {
+                    self.content.delete();
+                }
+                let change = Change::Delete(sel.beg(), sel.end() - sel.beg());
+                for other in self.others.iter_mut() {
+                    change.shift(other);
+                }
+                self.shift_marks(change);
+            }
+            _ => {
+                if grouped {
+                    self.content.move_to(main);
+                }
+            }
+        }
+        self.sel = None;
+        if grouped {
+            self.content.end_group();
+            self.merge_cursors();
+        }
+    }
+
+    fn set_mark(&mut self, id: usize, pos: usize) {
+        self.remove_mark(id);
+        self.marks.push((id, pos));
+    }
+
+    // clamped to the text, as marks can't be tracked through undo and redo
+    fn mark(&self, id: usize) -> Option<usize> {
+        self.marks
+            .iter()
+            .find(|&&(i, _)| i == id)
+            .map(|&(_, pos)| cmp::min(pos, self.content.len() - 1))
+    }
+
+    fn remove_mark(&mut self, id: usize) {
+        self.marks.retain(|&(i, _)| i != id);
     }
 }
 
-#[delegate(self.content)]
 impl<T> Editable for Select<T>
 where
-    T: Editable,
+    T: Editable + Undoable,
 {
-    fn step(&mut self, mov: Movement) -> ();
+    fn step(&mut self, mov: Movement) {
+        // an existing selection is extended by moving its head, otherwise cursors just move
+        let grouped = !self.others.is_empty();
+        if grouped {
+            self.content.begin_group();
+        }
+        let main = self.content.pos();
+        for other in self.others.iter_mut() {
+            self.content.move_to(other.head);
+            self.content.step(mov.clone());
+            other.head = self.content.pos();
+            if self.sel.is_none() {
+                other.anchor = other.head;
+            }
+        }
+        if grouped {
+            self.content.move_to(main);
+        }
+        self.content.step(mov);
+        if let Some(ref mut sel) = self.sel {
+            sel.head = self.content.pos();
+        }
+        if grouped {
+            self.content.end_group();
+            self.merge_cursors();
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        self.edit_all(|content| content.insert(c));
+    }
+
+    fn insert_forward(&mut self, c: char) {
+        self.edit_all(|content| content.insert_forward(c));
+    }
+
+    fn delete(&mut self) -> Option<char> {
+        let mut deleted = None;
+        self.edit_all(|content| deleted = content.delete());
+        deleted
+    }
+
+    fn delete_forward(&mut self) -> Option<char> {
+        let mut deleted = None;
+        self.edit_all(|content| deleted = content.delete_forward());
+        deleted
+    }
+
+    #[delegate(self.content)]
     fn move_to(&mut self, pos: usize) -> ();
+    #[delegate(self.content)]
     fn move_at(&mut self, line: usize, col: usize) -> ();
-    fn insert(&mut self, c: char) -> ();
-    fn insert_forward(&mut self, c: char) -> ();
-    fn delete(&mut self) -> Option<char>;
-    fn delete_forward(&mut self) -> Option<char>;
+    #[delegate(self.content)]
     fn pos(&self) -> usize;
+    #[delegate(self.content)]
     fn line(&self) -> usize;
+    #[delegate(self.content)]
     fn col(&self) -> usize;
+    #[delegate(self.content)]
     fn line_count(&self) -> usize;
+    #[delegate(self.content)]
     fn len(&self) -> usize;
-    fn iter(&self) -> CharIter;
-    fn lines(&self) -> LineIter;
-    fn iter_line(&self, line: usize) -> CharIter;
+    #[delegate(self.content)]
+    fn iter(&self) -> CharIter<'_>;
+    #[delegate(self.content)]
+    fn lines(&self) -> LineIter<'_>;
+    #[delegate(self.content)]
+    fn iter_line(&self, line: usize) -> CharIter<'_>;
+    #[delegate(self.content)]
     fn line_index_to_char_index(&self, line: usize) -> usize;
 }
 
@@ -90,20 +432,103 @@ where
     fn set_name(&mut self, name: String) -> ();
 }
 
-#[delegate(self.content)]
 impl<T> Undoable for Select<T>
 where
     T: Editable + Undoable,
 {
-    fn undo(&mut self) -> ();
-    fn redo(&mut self) -> ();
-    fn history_len(&self) -> usize;
+    // cursors can't be tracked through history so we fall back to the main one
+    fn undo(&mut self) {
+        self.sel = None;
+        self.others.clear();
+        self.content.undo();
+    }
+    fn redo(&mut self) {
+        self.sel = None;
+        self.others.clear();
+        self.content.redo();
+    }
+    #[delegate(self.content)]
+    fn begin_group(&mut self) -> ();
+    #[delegate(self.content)]
+    fn end_group(&mut self) -> ();
 }
 
 #[delegate(self.content)]
 impl<T> Modifiable for Select<T>
 where
-    T: Editable + Modifiable,
+    T: Editable + Undoable + Modifiable,
 {
     fn was_modified(&self) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Recorded, Text};
+
+    fn select(s: &str) -> Select<Recorded<Text>> {
+        let mut text = Text::empty();
+        for c in s.chars() {
+            text.insert(c);
+        }
+        Select::new(Recorded::new(text))
+    }
+
+    #[test]
+    fn marks_follow_edits() {
+        let mut content = select("abc def");
+        content.set_mark(1, 4);
+        content.set_mark(2, 2);
+
+        content.move_to(0);
+        content.insert('x');
+        content.insert('y');
+        assert_eq!(content.mark(1), Some(6));
+        assert_eq!(content.mark(2), Some(4));
+
+        // after the mark, nothing moves
+        content.move_to(8);
+        content.insert('z');
+        assert_eq!(content.mark(1), Some(6));
+
+        content.move_to(5);
+        content.delete();
+        content.delete();
+        assert_eq!(content.mark(1), Some(4));
+        assert_eq!(content.mark(2), Some(3));
+    }
+
+    #[test]
+    fn marks_in_deleted_text_go_to_its_start() {
+        let mut content = select("abc def");
+        content.set_mark(1, 5);
+        content.move_to(6);
+        content.set_sel(Selection::new(2, 6));
+        content.delete_sel();
+        assert_eq!(content.mark(1), Some(2));
+    }
+
+    #[test]
+    fn marks_are_replaced_and_removed() {
+        let mut content = select("abc");
+        assert_eq!(content.mark(1), None);
+        content.set_mark(1, 1);
+        content.set_mark(1, 2);
+        assert_eq!(content.mark(1), Some(2));
+        content.remove_mark(1);
+        assert_eq!(content.mark(1), None);
+    }
+
+    #[test]
+    fn marks_stay_in_text_after_undo() {
+        let mut content = select("");
+        content.begin_group();
+        for c in "abcdef".chars() {
+            content.insert(c);
+        }
+        content.end_group();
+        content.set_mark(1, 6);
+        content.undo();
+        assert_eq!(content.mark(1), Some(0));
+    }
+}
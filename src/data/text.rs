@@ -1,4 +1,4 @@
-use super::{CharIter, Editable, LineIter, Modifiable, Movement, Named, Saveable};
+use super::{is_word_char, CharIter, Editable, LineIter, Modifiable, Movement, Named, Saveable};
 use ropey::Rope;
 use std::cmp;
 use std::fs::File;
@@ -139,6 +139,23 @@ impl Editable for Text {
                 let curr_line_size = self.text.lines().nth(self.line()).unwrap().len_chars();
                 self.pos = curr_line + curr_line_size - 1;
             }
+            Movement::WordLeft => {
+                while self.pos > 0 && !is_word_char(self.text.char(self.pos - 1)) {
+                    self.pos -= 1;
+                }
+                while self.pos > 0 && is_word_char(self.text.char(self.pos - 1)) {
+                    self.pos -= 1;
+                }
+            }
+            Movement::WordRight => {
+                let last = self.text.len_chars() - 1;
+                while self.pos < last && !is_word_char(self.text.char(self.pos)) {
+                    self.pos += 1;
+                }
+                while self.pos < last && is_word_char(self.text.char(self.pos)) {
+                    self.pos += 1;
+                }
+            }
         }
     }
 
@@ -206,15 +223,15 @@ impl Editable for Text {
         self.text.len_chars()
     }
 
-    fn iter(&self) -> CharIter {
+    fn iter(&self) -> CharIter<'_> {
         self.text.chars()
     }
 
-    fn lines(&self) -> LineIter {
+    fn lines(&self) -> LineIter<'_> {
         self.text.lines()
     }
 
-    fn iter_line(&self, line: usize) -> CharIter {
+    fn iter_line(&self, line: usize) -> CharIter<'_> {
         self.text.line(line).chars()
     }
 
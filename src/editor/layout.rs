@@ -0,0 +1,326 @@
+use crate::view::Rect;
+use std::cmp;
+
+const MIN_RATIO: f32 = 0.1;
+const MAX_RATIO: f32 = 0.9;
+
+/// How the screen is divided between panes, by splitting it again and again in two
+#[derive(Debug, Clone)]
+pub enum Layout {
+    Pane(usize),
+    Split {
+        // side by side rather than one above the other
+        vertical: bool,
+        // share of the space given to the first part
+        ratio: f32,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Layout {
+    // Region of every pane, along with the columns separating side by side panes
+    pub fn regions(&self, area: Rect) -> (Vec<(usize, Rect)>, Vec<Rect>) {
+        let mut regions = Vec::new();
+        let mut separators = Vec::new();
+        self.collect_regions(area, &mut regions, &mut separators);
+        (regions, separators)
+    }
+
+    fn collect_regions(
+        &self,
+        area: Rect,
+        regions: &mut Vec<(usize, Rect)>,
+        separators: &mut Vec<Rect>,
+    ) {
+        match *self {
+            Layout::Pane(pane) => regions.push((pane, area)),
+            Layout::Split {
+                vertical,
+                ratio,
+                ref first,
+                ref second,
+            } => {
+                let (a, b) = if vertical {
+                    // one column is left for the separator
+                    let width = area.width.saturating_sub(1);
+                    let first_width = cmp::min((width as f32 * ratio).round() as usize, width);
+                    separators.push(Rect {
+                        x: area.x + first_width,
+                        width: cmp::min(1, area.width),
+                        ..area
+                    });
+                    (
+                        Rect {
+                            width: first_width,
+                            ..area
+                        },
+                        Rect {
+                            x: area.x + first_width + 1,
+                            width: width - first_width,
+                            ..area
+                        },
+                    )
+                } else {
+                    let first_height =
+                        cmp::min((area.height as f32 * ratio).round() as usize, area.height);
+                    (
+                        Rect {
+                            height: first_height,
+                            ..area
+                        },
+                        Rect {
+                            y: area.y + first_height,
+                            height: area.height - first_height,
+                            ..area
+                        },
+                    )
+                };
+                first.collect_regions(a, regions, separators);
+                second.collect_regions(b, regions, separators);
+            }
+        }
+    }
+
+    // Splits the space of pane in two, the new pane going second
+    pub fn split(&mut self, pane: usize, new_pane: usize, vertical: bool) {
+        match *self {
+            Layout::Pane(p) if p == pane => {
+                *self = Layout::Split {
+                    vertical,
+                    ratio: 0.5,
+                    first: Box::new(Layout::Pane(pane)),
+                    second: Box::new(Layout::Pane(new_pane)),
+                };
+            }
+            Layout::Pane(_) => {}
+            Layout::Split {
+                ref mut first,
+                ref mut second,
+                ..
+            } => {
+                first.split(pane, new_pane, vertical);
+                second.split(pane, new_pane, vertical);
+            }
+        }
+    }
+
+    // Gives the space of pane to its sibling, the panes after it moving down by one
+    pub fn remove(&mut self, pane: usize) {
+        self.detach(pane);
+        self.renumber(pane);
+    }
+
+    fn detach(&mut self, pane: usize) {
+        if let Layout::Split {
+            ref mut first,
+            ref mut second,
+            ..
+        } = *self
+        {
+            let sibling = match (&**first, &**second) {
+                (&Layout::Pane(p), _) if p == pane => Some((**second).clone()),
+                (_, &Layout::Pane(p)) if p == pane => Some((**first).clone()),
+                _ => None,
+            };
+            match sibling {
+                Some(sibling) => *self = sibling,
+                None => {
+                    first.detach(pane);
+                    second.detach(pane);
+                }
+            }
+        }
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match *self {
+            Layout::Pane(ref mut p) if *p > removed => *p -= 1,
+            Layout::Pane(_) => {}
+            Layout::Split {
+                ref mut first,
+                ref mut second,
+                ..
+            } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    // Grows or shrinks pane by changing the split closest to it, returning false if it isn't split
+    pub fn resize(&mut self, pane: usize, delta: f32) -> bool {
+        match *self {
+            Layout::Pane(_) => false,
+            Layout::Split {
+                ref mut ratio,
+                ref mut first,
+                ref mut second,
+                ..
+            } => {
+                if first.resize(pane, delta) || second.resize(pane, delta) {
+                    return true;
+                }
+                if first.contains(pane) {
+                    *ratio = (*ratio + delta).clamp(MIN_RATIO, MAX_RATIO);
+                    true
+                } else if second.contains(pane) {
+                    *ratio = (*ratio - delta).clamp(MIN_RATIO, MAX_RATIO);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    fn contains(&self, pane: usize) -> bool {
+        match *self {
+            Layout::Pane(p) => p == pane,
+            Layout::Split {
+                ref first,
+                ref second,
+                ..
+            } => first.contains(pane) || second.contains(pane),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 81,
+        height: 40,
+    };
+
+    fn panes(layout: &Layout) -> Vec<usize> {
+        let (regions, _) = layout.regions(AREA);
+        regions.into_iter().map(|(pane, _)| pane).collect()
+    }
+
+    // Splits pane 0 to the right, then each of the two panes downwards
+    fn grid() -> Layout {
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, true);
+        layout.split(0, 2, false);
+        layout.split(1, 3, false);
+        layout
+    }
+
+    #[test]
+    fn split_divides_the_pane() {
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, true);
+        let (regions, separators) = layout.regions(AREA);
+        assert_eq!(
+            regions,
+            vec![
+                (0, Rect { width: 40, ..AREA }),
+                (
+                    1,
+                    Rect {
+                        x: 41,
+                        width: 40,
+                        ..AREA
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            separators,
+            vec![Rect {
+                x: 40,
+                width: 1,
+                ..AREA
+            }]
+        );
+
+        layout.split(1, 2, false);
+        let (regions, separators) = layout.regions(AREA);
+        assert_eq!(
+            regions[1].1,
+            Rect {
+                x: 41,
+                width: 40,
+                height: 20,
+                ..AREA
+            }
+        );
+        assert_eq!(
+            regions[2].1,
+            Rect {
+                x: 41,
+                y: 20,
+                width: 40,
+                height: 20
+            }
+        );
+        assert_eq!(separators.len(), 1);
+    }
+
+    #[test]
+    fn split_ignores_missing_pane() {
+        let mut layout = Layout::Pane(0);
+        layout.split(1, 2, true);
+        assert_eq!(panes(&layout), vec![0]);
+    }
+
+    #[test]
+    fn remove_renumbers_once() {
+        let mut layout = grid();
+        assert_eq!(panes(&layout), vec![0, 2, 1, 3]);
+        layout.remove(0);
+        assert_eq!(panes(&layout), vec![1, 0, 2]);
+        layout.remove(2);
+        assert_eq!(panes(&layout), vec![1, 0]);
+        layout.remove(1);
+        assert_eq!(panes(&layout), vec![0]);
+    }
+
+    #[test]
+    fn remove_gives_space_to_sibling() {
+        let mut layout = grid();
+        layout.remove(2);
+        let (regions, _) = layout.regions(AREA);
+        assert_eq!(regions[0], (0, Rect { width: 40, ..AREA }));
+    }
+
+    #[test]
+    fn remove_last_pane_keeps_it() {
+        let mut layout = Layout::Pane(0);
+        layout.remove(0);
+        assert_eq!(panes(&layout), vec![0]);
+    }
+
+    #[test]
+    fn resize_changes_closest_split() {
+        let mut layout = grid();
+        assert!(layout.resize(3, 0.2));
+        let (regions, _) = layout.regions(AREA);
+        // pane 3 is second in its split, so growing it moves the split up
+        assert_eq!(regions[3].1.y, 12);
+        assert_eq!(regions[3].1.height, 28);
+        // the side by side split is left alone
+        assert_eq!(regions[3].1.x, 41);
+    }
+
+    #[test]
+    fn resize_clamps_ratio() {
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, false);
+        assert!(layout.resize(0, 5.0));
+        let (regions, _) = layout.regions(AREA);
+        assert_eq!(regions[0].1.height, 36);
+    }
+
+    #[test]
+    fn resize_without_split() {
+        let mut layout = Layout::Pane(0);
+        assert!(!layout.resize(0, 0.1));
+        assert!(!grid().resize(7, 0.1));
+    }
+}
@@ -0,0 +1,588 @@
+mod layout;
+mod session;
+mod tabs;
+
+use self::layout::Layout;
+pub use self::session::load_session;
+use self::session::Recent;
+use self::tabs::TAB_BAR_HEIGHT;
+use crate::command::{self, Context, PaneAction, State};
+use crate::config::{Config, Overrides, Settings};
+use crate::data::{
+    display_name, same_file, Editable, Modifiable, Named, Recorded, Select, Selectable, Text,
+};
+use crate::view::{self, Rect, Screen, View};
+use std::cmp;
+use std::io::{stdin, Result};
+use std::rc::Rc;
+use syntect::highlighting::{Color, FontStyle, Style, Theme};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use termion::event::Key;
+use termion::input::TermRead;
+
+// how much of its split a pane grows or shrinks by at once
+const RESIZE_STEP: f32 = 0.05;
+
+// An open file
+struct Buffer<'a> {
+    text: Select<Recorded<Text>>,
+    syntax: &'a SyntaxReference,
+    // settings changed from the command line, kept whatever the syntax
+    overrides: Overrides,
+}
+
+// A part of the screen showing a buffer, several panes possibly showing the same one
+struct Pane<'a> {
+    buffer: usize,
+    view: View<'a>,
+    // mark keeping where the cursor was in the buffer when the pane last lost focus
+    mark: usize,
+}
+
+/// Holds the open buffers and the panes showing them, passing events to the focused pane and
+/// acting on the signals sent back
+pub struct Editor<'a> {
+    buffers: Vec<Buffer<'a>>,
+    panes: Vec<Pane<'a>>,
+    layout: Layout,
+    focus: usize,
+    screen: Rc<Screen>,
+    syntax_set: &'a SyntaxSet,
+    theme: &'a Theme,
+    config: Config,
+    recent: Recent,
+    // mark to give the next pane
+    next_mark: usize,
+}
+
+impl<'a> Editor<'a> {
+    pub fn new(syntax_set: &'a SyntaxSet, theme: &'a Theme, config: Config) -> Self {
+        Editor {
+            buffers: Vec::new(),
+            panes: Vec::new(),
+            layout: Layout::Pane(0),
+            focus: 0,
+            screen: view::open_screen(theme),
+            syntax_set,
+            theme,
+            config,
+            recent: Recent::load(),
+            next_mark: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    // Shows the buffer of a file in the focused pane, opening it if needed, or a new empty buffer,
+    // returning its index
+    pub fn open(
+        &mut self,
+        filename: Option<String>,
+        position: Option<(usize, usize)>,
+    ) -> Result<usize> {
+        let open = filename.as_ref().and_then(|name| {
+            // the recent files and the session name files by their absolute path
+            self.buffers
+                .iter()
+                .position(|b| same_file(b.text.name(), name))
+        });
+        let is_new = open.is_none();
+        let index = match open {
+            Some(i) => i,
+            None => {
+                let text = match filename {
+                    Some(ref name) => Text::open_file(name.clone())?,
+                    None => Text::empty(),
+                };
+                self.buffers.push(Buffer {
+                    text: Select::new(Recorded::new(text)),
+                    syntax: self.find_syntax(&filename),
+                    overrides: Overrides::default(),
+                });
+                self.buffers.len() - 1
+            }
+        };
+        self.switch_to(index);
+        if let Some((line, col)) = position {
+            let pane = &mut self.panes[self.focus];
+            let text = &mut self.buffers[pane.buffer].text;
+            text.move_at(line, col);
+            pane.view.center_view(text.line());
+        } else if is_new {
+            // back to where we left the file
+            let recent = &self.recent;
+            if let Some(last) = filename.as_ref().and_then(|name| recent.get(name)) {
+                self.buffers[index].text.move_at(last.line, last.col);
+                self.panes[self.focus].view.set_line_offset(last.scroll);
+            }
+        }
+        self.remember(index);
+        Ok(index)
+    }
+
+    // Shows a buffer in the focused pane
+    pub fn switch_to(&mut self, index: usize) {
+        if self.panes.is_empty() {
+            let view = self.build_view(index);
+            let mark = self.new_mark();
+            self.panes.push(Pane {
+                buffer: index,
+                view,
+                mark,
+            });
+        } else if self.panes[self.focus].buffer != index {
+            let view = self.build_view(index);
+            let pane = &mut self.panes[self.focus];
+            pane.buffer = index;
+            pane.view = view;
+        }
+        self.update_regions();
+        let pane = &mut self.panes[self.focus];
+        let text = &self.buffers[index].text;
+        pane.view.center_view(text.line());
+    }
+
+    // Shows a message in the focused pane
+    pub fn message(&mut self, message: &str) {
+        self.panes[self.focus].view.message(message);
+    }
+
+    pub fn run(&mut self, ctx: &mut Context) {
+        let stdin = stdin();
+        let mut events = stdin.events();
+        let mut state = self.enter_mode(State::Insert, ctx);
+
+        self.render();
+        loop {
+            if let Some(event) = events.next() {
+                let event = event.unwrap();
+                let shown = (self.focus, self.panes[self.focus].buffer);
+                // clicking in another pane focuses it, and clicking a tab shows its buffer,
+                // unless something is going on in the focused pane
+                let mut clicked_tab = None;
+                if let State::Insert
+                | State::Message
+                | State::Selected
+                | State::Normal(_)
+                | State::ViInsert
+                | State::Visual(_) = state
+                {
+                    if let Some((x, y)) = command::pressed_at(&event) {
+                        clicked_tab = self.tab_at(x, y);
+                        if let Some(pane) = self.pane_at(x, y) {
+                            self.focus_pane(pane);
+                        }
+                    }
+                }
+
+                state = match clicked_tab {
+                    Some(buffer) => State::SwitchBuffer(buffer),
+                    None => {
+                        let pane = &mut self.panes[self.focus];
+                        let buffer = pane.buffer;
+                        let text = &mut self.buffers[buffer].text;
+                        let name = text.name().clone();
+                        let state = state.handle(text, &mut pane.view, ctx, event);
+                        if *text.name() != name {
+                            self.detect_syntax(buffer);
+                        }
+                        state
+                    }
+                };
+                state = match self.signal(state) {
+                    Some(state) => {
+                        // insert mode belongs to the pane and the buffer it was entered in
+                        if (self.focus, self.panes[self.focus].buffer) != shown {
+                            if let Some(ref mut vi) = ctx.vi {
+                                vi.leave_insert();
+                            }
+                        }
+                        self.enter_mode(state, ctx)
+                    }
+                    None => break,
+                };
+            }
+
+            // no need to show a paste until it has been received entirely
+            if let State::Paste(_) = state {
+                continue;
+            }
+            self.render();
+        }
+        self.save_state();
+    }
+
+    // Shows the mode the state is in, in the focused pane
+    fn enter_mode(&mut self, state: State, ctx: &Context) -> State {
+        let pane = &mut self.panes[self.focus];
+        let state = state.in_mode(&mut self.buffers[pane.buffer].text, ctx);
+        if let Some((mode, cursor_shape)) = state.mode(ctx) {
+            pane.view.set_mode(mode, cursor_shape);
+        }
+        state
+    }
+
+    // Remembers where we are in every file, and which ones are open for the next session
+    fn save_state(&mut self) {
+        let focused = self.panes[self.focus].buffer;
+        for buffer in (0..self.buffers.len()).filter(|&b| b != focused) {
+            self.remember(buffer);
+        }
+        self.remember(focused);
+        let names: Vec<String> = self.buffers.iter().map(|b| b.text.name().clone()).collect();
+        // there is nowhere left to report a failure, the terminal being given back
+        let _ = self.recent.save();
+        let _ = session::save_session(&names);
+    }
+
+    // Moves a file to the top of the recent ones, along with where we are in it
+    fn remember(&mut self, buffer: usize) {
+        let text = &self.buffers[buffer].text;
+        if text.name().is_empty() {
+            return;
+        }
+        // the focused pane is the one the cursor of the buffer belongs to
+        let pane = if self.panes[self.focus].buffer == buffer {
+            Some(&self.panes[self.focus])
+        } else {
+            self.panes.iter().find(|p| p.buffer == buffer)
+        };
+        let scroll = pane.map_or(0, |p| p.view.line_offset());
+        self.recent
+            .touch(text.name(), text.line(), text.col(), scroll);
+    }
+
+    fn render(&mut self) {
+        self.screen.clear();
+        let separators = self.update_regions();
+        self.paint_tabs();
+
+        let style = Style {
+            foreground: self
+                .theme
+                .settings
+                .gutter_foreground
+                .unwrap_or(Color::WHITE),
+            background: self.theme.settings.background.unwrap_or(Color::BLACK),
+            font_style: FontStyle::empty(),
+        };
+        for separator in separators {
+            for y in separator.y..separator.y + separator.height {
+                self.screen
+                    .draw_with_style(separator.x, y, style, "\u{2502}");
+            }
+        }
+
+        // the focused pane goes last so that its cursor and messages stay on top
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            if i != self.focus {
+                pane.view.render(&self.buffers[pane.buffer].text, false);
+            }
+        }
+        let pane = &mut self.panes[self.focus];
+        pane.view.render(&self.buffers[pane.buffer].text, true);
+        self.screen.present();
+    }
+
+    // Acts on what the state asks of the editor, returning None once it should exit
+    fn signal(&mut self, state: State) -> Option<State> {
+        Some(match state {
+            State::Open(filename) => {
+                let msg = match self.open(Some(filename.clone()), None) {
+                    Ok(_) => format!("Opened {}", filename),
+                    Err(e) => e.to_string(),
+                };
+                self.message(&msg);
+                State::Message
+            }
+            State::Close => {
+                let index = self.panes[self.focus].buffer;
+                let name = display_name(&self.buffers[index].text);
+                if !self.close_buffer(index) {
+                    return None;
+                }
+                self.message(&format!("Closed {}", name));
+                State::Message
+            }
+            State::QuitAll(discarded) => {
+                // the modified buffers are shown one after the other to decide on their changes
+                let modified: Vec<usize> = (0..self.buffers.len())
+                    .filter(|&i| self.buffers[i].text.was_modified() && !discarded.contains(&i))
+                    .collect();
+                let buffer = match modified.first() {
+                    Some(&buffer) => buffer,
+                    None => return None,
+                };
+                self.switch_to(buffer);
+                let names = modified
+                    .iter()
+                    .map(|&i| display_name(&self.buffers[i].text))
+                    .collect();
+                let view = &mut self.panes[self.focus].view;
+                State::confirm_quit(view, names, buffer, discarded)
+            }
+            State::CycleBuffer(offset) => {
+                let count = self.buffers.len() as isize;
+                let current = self.panes[self.focus].buffer as isize;
+                self.switch_to((current + offset).rem_euclid(count) as usize);
+                self.switched()
+            }
+            State::ListBuffers => {
+                let names = self
+                    .buffers
+                    .iter()
+                    .map(|b| {
+                        let mut name = display_name(&b.text);
+                        if b.text.was_modified() {
+                            name.push_str(" *");
+                        }
+                        name
+                    })
+                    .collect();
+                let pane = &mut self.panes[self.focus];
+                State::pick_buffer(&mut pane.view, names, pane.buffer)
+            }
+            State::ListRecent => {
+                let paths = self.recent.paths();
+                State::pick_recent(&mut self.panes[self.focus].view, paths)
+            }
+            State::SwitchBuffer(i) => {
+                self.switch_to(i);
+                self.switched()
+            }
+            State::Pane(action) => self.pane_action(action),
+            State::Set(overrides) => {
+                let buffer = self.panes[self.focus].buffer;
+                self.buffers[buffer].overrides.merge(overrides);
+                let settings = self.settings(buffer);
+                for pane in self.panes.iter_mut().filter(|p| p.buffer == buffer) {
+                    *pane.view.settings_mut() = settings.clone();
+                }
+                State::Insert
+            }
+            state => state,
+        })
+    }
+
+    fn switched(&mut self) -> State {
+        let index = self.panes[self.focus].buffer;
+        let msg = format!(
+            "Buffer {}/{}: {}",
+            index + 1,
+            self.buffers.len(),
+            display_name(&self.buffers[index].text)
+        );
+        self.message(&msg);
+        State::Message
+    }
+
+    fn pane_action(&mut self, action: PaneAction) -> State {
+        match action {
+            PaneAction::Split(vertical) => {
+                let mark = self.new_mark();
+                let current = &self.panes[self.focus];
+                let mut view = self.build_view(current.buffer);
+                view.set_region(current.view.region());
+                let text = &mut self.buffers[current.buffer].text;
+                let pos = text.pos();
+                text.set_mark(mark, pos);
+                let pane = Pane {
+                    buffer: current.buffer,
+                    view,
+                    mark,
+                };
+                self.panes.push(pane);
+                let new_pane = self.panes.len() - 1;
+                self.layout.split(self.focus, new_pane, vertical);
+                self.focus_pane(new_pane);
+                self.update_regions();
+                let pane = &mut self.panes[self.focus];
+                pane.view.center_view(self.buffers[pane.buffer].text.line());
+                State::Insert
+            }
+            PaneAction::Focus(direction) => {
+                if let Some(pane) = self.pane_towards(direction) {
+                    self.focus_pane(pane);
+                }
+                State::Insert
+            }
+            PaneAction::Resize(steps) => {
+                if !self.layout.resize(self.focus, steps as f32 * RESIZE_STEP) {
+                    self.message("There is only one pane");
+                    return State::Message;
+                }
+                State::Insert
+            }
+            PaneAction::Close => {
+                if self.panes.len() == 1 {
+                    self.message("Can't close the last pane");
+                    return State::Message;
+                }
+                self.close_pane(self.focus);
+                State::Insert
+            }
+        }
+    }
+
+    // Lays the panes out on the screen, returning the columns left to separate them
+    fn update_regions(&mut self) -> Vec<Rect> {
+        let (regions, separators) = self.layout.regions(Rect::main_area(TAB_BAR_HEIGHT));
+        for (pane, region) in regions {
+            self.panes[pane].view.set_region(region);
+        }
+        separators
+    }
+
+    // Moves the focus to another pane, each pane having its own cursor
+    fn focus_pane(&mut self, pane: usize) {
+        if pane == self.focus {
+            return;
+        }
+        let old = &self.panes[self.focus];
+        let text = &mut self.buffers[old.buffer].text;
+        let pos = text.pos();
+        text.set_mark(old.mark, pos);
+        self.focus = pane;
+
+        let new = &self.panes[pane];
+        let text = &mut self.buffers[new.buffer].text;
+        let pos = text.mark(new.mark).unwrap_or_else(|| text.pos());
+        if text.pos() != pos {
+            text.reset_sel();
+            text.reset_cursors();
+            text.move_to(pos);
+        }
+    }
+
+    fn close_pane(&mut self, pane: usize) {
+        self.layout.remove(pane);
+        let closed = self.panes.remove(pane);
+        self.buffers[closed.buffer].text.remove_mark(closed.mark);
+        if pane < self.focus {
+            self.focus -= 1;
+        } else if pane == self.focus {
+            // focus whatever pane comes in its place, without saving the closed one's cursor
+            self.focus = cmp::min(pane, self.panes.len() - 1);
+            let new = &self.panes[self.focus];
+            let text = &mut self.buffers[new.buffer].text;
+            if let Some(pos) = text.mark(new.mark) {
+                text.move_to(pos);
+            }
+        }
+    }
+
+    // Closes a buffer along with the panes showing it, returning false if it was the last one
+    fn close_buffer(&mut self, index: usize) -> bool {
+        self.remember(index);
+        if self.buffers.len() == 1 {
+            return false;
+        }
+        while self.panes.len() > 1 {
+            match self.panes.iter().position(|p| p.buffer == index) {
+                Some(pane) => self.close_pane(pane),
+                None => break,
+            }
+        }
+        self.buffers.remove(index);
+        for i in 0..self.panes.len() {
+            let pane = &mut self.panes[i];
+            if pane.buffer > index {
+                pane.buffer -= 1;
+            } else if pane.buffer == index {
+                // the last pane left shows another buffer
+                let buffer = cmp::min(index, self.buffers.len() - 1);
+                let view = self.build_view(buffer);
+                let pane = &mut self.panes[i];
+                pane.buffer = buffer;
+                pane.view = view;
+                pane.view.center_view(self.buffers[buffer].text.line());
+            }
+        }
+        true
+    }
+
+    fn new_mark(&mut self) -> usize {
+        self.next_mark += 1;
+        self.next_mark
+    }
+
+    fn pane_at(&self, x: u16, y: u16) -> Option<usize> {
+        self.panes
+            .iter()
+            .position(|p| p.view.region().contains(x, y))
+    }
+
+    // Closest pane next to the focused one in a direction
+    fn pane_towards(&self, direction: Key) -> Option<usize> {
+        let from = self.panes[self.focus].view.region();
+        let overlap =
+            |a: usize, a_len: usize, b: usize, b_len: usize| a < b + b_len && b < a + a_len;
+        self.panes
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != self.focus)
+            .filter_map(|(i, pane)| {
+                let to = pane.view.region();
+                let beside = overlap(from.y, from.height, to.y, to.height);
+                let above_below = overlap(from.x, from.width, to.x, to.width);
+                let distance = match direction {
+                    Key::Left if beside && to.x + to.width <= from.x => from.x - to.x - to.width,
+                    Key::Right if beside && to.x >= from.x + from.width => {
+                        to.x - from.x - from.width
+                    }
+                    Key::Up if above_below && to.y + to.height <= from.y => {
+                        from.y - to.y - to.height
+                    }
+                    Key::Down if above_below && to.y >= from.y + from.height => {
+                        to.y - from.y - from.height
+                    }
+                    _ => return None,
+                };
+                Some((distance, i))
+            })
+            .min()
+            .map(|(_, i)| i)
+    }
+
+    // Highlights a buffer according to its new name
+    fn detect_syntax(&mut self, buffer: usize) {
+        let name = Some(self.buffers[buffer].text.name().clone());
+        let syntax = self.find_syntax(&name);
+        self.buffers[buffer].syntax = syntax;
+        let settings = self.settings(buffer);
+        for pane in self.panes.iter_mut().filter(|p| p.buffer == buffer) {
+            pane.view.set_syntax(syntax, settings.clone());
+        }
+    }
+
+    // Settings of the language of a buffer, along with those set for it
+    fn settings(&self, buffer: usize) -> Settings {
+        let buffer = &self.buffers[buffer];
+        let mut settings = self.config.settings(&buffer.syntax.name);
+        buffer.overrides.apply(&mut settings);
+        settings
+    }
+
+    fn build_view(&self, buffer: usize) -> View<'a> {
+        View::new(
+            self.theme,
+            self.buffers[buffer].syntax,
+            self.syntax_set,
+            self.settings(buffer),
+            self.screen.clone(),
+        )
+    }
+
+    fn find_syntax(&self, filename: &Option<String>) -> &'a SyntaxReference {
+        let syntax_set = self.syntax_set;
+        match filename {
+            Some(filename) => match syntax_set.find_syntax_for_file(filename) {
+                Ok(Some(syn)) => syn,
+                _ => syntax_set.find_syntax_plain_text(),
+            },
+            None => syntax_set.find_syntax_plain_text(),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Result, Write};
+use std::path::{Path, PathBuf};
+
+// how many files are remembered
+const MAX_RECENT: usize = 100;
+const RECENT_FILE: &str = "recent";
+const SESSION_FILE: &str = "session";
+
+/// Where we were in a file when we last left it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePosition {
+    pub path: String,
+    pub line: usize,
+    pub col: usize,
+    // first line shown
+    pub scroll: usize,
+}
+
+/// Files opened before, the most recent one first
+pub struct Recent {
+    files: Vec<FilePosition>,
+}
+
+impl Recent {
+    // Reads the files from the state directory, the list staying empty if there are none
+    pub fn load() -> Self {
+        Recent::from_lines(&read_state(RECENT_FILE))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write_state(RECENT_FILE, &self.to_lines())
+    }
+
+    fn from_lines(lines: &[String]) -> Self {
+        let files = lines
+            .iter()
+            .filter_map(|line| {
+                // the path goes last as it may contain tabs
+                let mut fields = line.splitn(4, '\t');
+                Some(FilePosition {
+                    line: fields.next()?.parse().ok()?,
+                    col: fields.next()?.parse().ok()?,
+                    scroll: fields.next()?.parse().ok()?,
+                    path: fields.next()?.to_string(),
+                })
+            })
+            .collect();
+        Recent { files }
+    }
+
+    fn to_lines(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .map(|f| format!("{}\t{}\t{}\t{}", f.line, f.col, f.scroll, f.path))
+            .collect()
+    }
+
+    pub fn get(&self, path: &str) -> Option<&FilePosition> {
+        let path = absolute(path)?;
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    // Puts a file at the top of the list, with where we are in it
+    pub fn touch(&mut self, path: &str, line: usize, col: usize, scroll: usize) {
+        let path = match absolute(path) {
+            Some(path) => path,
+            None => return,
+        };
+        self.files.retain(|f| f.path != path);
+        self.files.insert(
+            0,
+            FilePosition {
+                path,
+                line,
+                col,
+                scroll,
+            },
+        );
+        self.files.truncate(MAX_RECENT);
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.files.iter().map(|f| f.path.clone()).collect()
+    }
+}
+
+// Files that were open when the editor was last quit
+pub fn load_session() -> Vec<String> {
+    read_state(SESSION_FILE)
+}
+
+pub fn save_session(paths: &[String]) -> Result<()> {
+    let paths: Vec<String> = paths.iter().filter_map(|p| absolute(p)).collect();
+    write_state(SESSION_FILE, &paths)
+}
+
+// Directory the editor keeps its state in, following the XDG base directories
+fn state_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("smith"))
+}
+
+fn read_state(name: &str) -> Vec<String> {
+    match state_dir() {
+        Some(dir) => read_lines(&dir.join(name)),
+        None => Vec::new(),
+    }
+}
+
+fn write_state(name: &str, lines: &[String]) -> Result<()> {
+    match state_dir() {
+        Some(dir) => write_lines(&dir.join(name), lines),
+        None => Ok(()),
+    }
+}
+
+fn read_lines(path: &Path) -> Vec<String> {
+    match File::open(path) {
+        Ok(file) => BufReader::new(file).lines().map_while(|l| l.ok()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = File::create(path)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+// Files are remembered by their absolute path, so that they are found from anywhere
+fn absolute(path: &str) -> Option<String> {
+    fs::canonicalize(path)
+        .ok()
+        .and_then(|p| p.to_str().map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    // A directory with the given files in it, removed once dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let dir = env::temp_dir().join(format!("smith-{}-{}", name, process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            for file in files {
+                fs::write(dir.join(file), "").unwrap();
+            }
+            TempDir(fs::canonicalize(dir).unwrap())
+        }
+
+        fn path(&self, file: &str) -> String {
+            self.0.join(file).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn touch_puts_file_first() {
+        let dir = TempDir::new("touch", &["a", "b"]);
+        let mut recent = Recent { files: Vec::new() };
+        recent.touch(&dir.path("a"), 1, 2, 3);
+        recent.touch(&dir.path("b"), 4, 5, 6);
+        recent.touch(&dir.path("a"), 7, 8, 9);
+        assert_eq!(recent.paths(), vec![dir.path("a"), dir.path("b")]);
+        let a = recent.get(&dir.path("a")).unwrap();
+        assert_eq!((a.line, a.col, a.scroll), (7, 8, 9));
+        // found however the path is spelled, but only if the file exists
+        assert!(recent.get(&dir.path("./b")).is_some());
+        recent.touch(&dir.path("missing"), 0, 0, 0);
+        assert_eq!(recent.paths().len(), 2);
+    }
+
+    #[test]
+    fn keeps_the_most_recent_files() {
+        let names: Vec<String> = (0..MAX_RECENT + 3).map(|i| i.to_string()).collect();
+        let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+        let dir = TempDir::new("truncate", &name_refs);
+        let mut recent = Recent { files: Vec::new() };
+        for name in &names {
+            recent.touch(&dir.path(name), 0, 0, 0);
+        }
+        let paths = recent.paths();
+        assert_eq!(paths.len(), MAX_RECENT);
+        assert_eq!(paths[0], dir.path(&(MAX_RECENT + 2).to_string()));
+        assert_eq!(paths[MAX_RECENT - 1], dir.path("3"));
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        let dir = TempDir::new("lines", &["a", "with\ttab"]);
+        let mut recent = Recent { files: Vec::new() };
+        recent.touch(&dir.path("a"), 10, 2, 4);
+        recent.touch(&dir.path("with\ttab"), 1, 0, 0);
+
+        let file = dir.0.join("state").join(RECENT_FILE);
+        write_lines(&file, &recent.to_lines()).unwrap();
+        let loaded = Recent::from_lines(&read_lines(&file));
+        assert_eq!(loaded.files, recent.files);
+    }
+
+    #[test]
+    fn skips_broken_lines() {
+        let lines: Vec<String> = vec!["1\t2\t3\t/a".into(), "x\t2\t3\t/b".into(), "1\t2".into()];
+        let recent = Recent::from_lines(&lines);
+        assert_eq!(recent.paths(), vec!["/a"]);
+        assert_eq!(
+            recent.files[0],
+            FilePosition {
+                path: "/a".to_string(),
+                line: 1,
+                col: 2,
+                scroll: 3
+            }
+        );
+    }
+
+    #[test]
+    fn missing_file_reads_empty() {
+        let dir = TempDir::new("missing", &[]);
+        assert!(read_lines(&dir.0.join("nothing")).is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use super::Editor;
+use crate::data::{Modifiable, Named};
+use std::path::Path;
+use syntect::highlighting::{Color, FontStyle, Style};
+use termion::terminal_size;
+
+// rows taken by the tab bar at the top of the screen
+pub const TAB_BAR_HEIGHT: usize = 1;
+
+impl<'a> Editor<'a> {
+    // Tab of every buffer as its index, first column and label, scrolled so that the tab of the
+    // focused buffer fits in the given width
+    fn tabs(&self, width: usize) -> Vec<(usize, usize, String)> {
+        let labels: Vec<String> = self
+            .buffers
+            .iter()
+            .map(|b| {
+                let name = b.text.name();
+                let mut label = match Path::new(name).file_name() {
+                    Some(file_name) => file_name.to_string_lossy().into_owned(),
+                    None if name.is_empty() => "[No Name]".to_string(),
+                    None => name.clone(),
+                };
+                if b.text.was_modified() {
+                    label.push_str(" *");
+                }
+                format!(" {} ", label)
+            })
+            .collect();
+
+        let active = self.panes[self.focus].buffer;
+        let end_of_active: usize = labels[..=active].iter().map(|l| l.chars().count()).sum();
+        let scroll = end_of_active.saturating_sub(width);
+
+        let mut x = 0;
+        let mut tabs = Vec::new();
+        for (i, label) in labels.into_iter().enumerate() {
+            let len = label.chars().count();
+            if x >= scroll {
+                tabs.push((i, x - scroll, label));
+            }
+            x += len;
+        }
+        tabs
+    }
+
+    pub(super) fn paint_tabs(&self) {
+        let (width, _) = terminal_size().unwrap();
+        let width = width as usize;
+        let settings = &self.theme.settings;
+        let style = Style {
+            foreground: settings.gutter_foreground.unwrap_or(Color::WHITE),
+            background: settings.gutter.unwrap_or(Color::BLACK),
+            font_style: FontStyle::empty(),
+        };
+        let active_style = Style {
+            foreground: settings.foreground.unwrap_or(Color::WHITE),
+            background: settings.background.unwrap_or(Color::BLACK),
+            font_style: FontStyle::empty(),
+        };
+
+        let empty_line: String = (0..width).map(|_| ' ').collect();
+        self.screen.draw_with_style(0, 0, style, &empty_line);
+        let active = self.panes[self.focus].buffer;
+        for (buffer, x, label) in self.tabs(width) {
+            let style = if buffer == active {
+                active_style
+            } else {
+                style
+            };
+            self.screen
+                .draw_ranges_within(x, 0, width, vec![(style, &label)]);
+        }
+    }
+
+    // Buffer whose tab is at the given 1-based terminal coordinates
+    pub(super) fn tab_at(&self, x: u16, y: u16) -> Option<usize> {
+        if y as usize > TAB_BAR_HEIGHT {
+            return None;
+        }
+        let (width, _) = terminal_size().unwrap();
+        let x = (x as usize).checked_sub(1)?;
+        self.tabs(width as usize)
+            .into_iter()
+            .find(|(_, beg, label)| x >= *beg && x < beg + label.chars().count())
+            .map(|(buffer, _, _)| buffer)
+    }
+}
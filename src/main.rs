@@ -1,80 +1,238 @@
 mod command;
+mod config;
 mod data;
+mod editor;
 mod view;
 
-use command::State;
-use data::{Recorded, Select, Text};
+use command::{Context, Vi};
+use config::Config;
+use editor::Editor;
 use std::env;
-use std::io::stdin;
-use syntect::dumps::from_binary;
-use syntect::highlighting::Theme;
+use std::path::Path;
 use syntect::parsing::SyntaxSet;
-use termion::input::TermRead;
-use view::View;
+
+// a file to edit, with the line and column to start at
+type FileArg = (String, Option<(usize, usize)>);
 
 fn main() {
-    let args = env::args();
+    let (files, restore) = parse_args(env::args().skip(1));
+    // the files of the previous session come before those given
+    let session = if restore {
+        editor::load_session()
+    } else {
+        Vec::new()
+    };
+    let mut errors = Vec::new();
+    // a broken config file is reported, the defaults being used meanwhile
+    let config = Config::load().unwrap_or_else(|e| {
+        errors.push(e);
+        Config::default()
+    });
+    errors.extend(
+        config
+            .unknown_keys()
+            .iter()
+            .map(|key| format!("Unknown config key {}", key)),
+    );
+    let (ts, theme_error) = config.theme();
+    errors.extend(theme_error);
 
-    if args.len() > 1 {
-        for filename in args.skip(1) {
-            edit_file(&Some(filename));
+    let mut ctx = Context::new();
+    // unbinding first, so that the keys unbound can start sequences
+    let mut bindings: Vec<_> = config.keys().iter().collect();
+    bindings.sort_by_key(|&(keys, command)| (command != "none", keys));
+    for (keys, command) in bindings {
+        if let Err(e) = ctx.keymap.bind(keys, command) {
+            errors.push(format!("{} = {}: {}", keys, command, e));
         }
-    } else {
-        edit_file(&None);
     }
-}
-
-fn edit_file(filename: &Option<String>) {
+    for (key, command) in config.prompt_keys() {
+        if let Err(e) = ctx.keymap.bind_prompt(key, command) {
+            errors.push(format!("{} = {}: {}", key, command, e));
+        }
+    }
+    if config.vi_mode() {
+        ctx.vi = Some(Vi::default());
+    }
     let ps = SyntaxSet::load_defaults_nonewlines();
-    let ts: Theme = from_binary(include_bytes!("../assets/gruvbox.themedump"));
-    let mut text = build_text(&filename);
-    let mut view = build_view(&filename, &ps, &ts);
-    let mut state = State::Insert;
-
-    let stdin = stdin();
-
-    view.render(&text);
-
-    let mut events = stdin.events();
-    loop {
-        if let Some(event) = events.next() {
-            state = match state.handle(&mut text, &mut view, event.unwrap()) {
-                State::Exit => break,
-                State::Open(new_filename) => {
-                    // we must close the terminal modes before resetting them
-                    drop(text);
-                    drop(view);
-                    text = build_text(&Some(new_filename.clone()));
-                    view = build_view(&Some(new_filename.clone()), &ps, &ts);
-                    view.message(&format!("Opened {}", new_filename));
-                    State::Insert
-                }
-                state => state,
-            }
+    let mut editor = Editor::new(&ps, &ts, config);
+
+    let first_given = session.len();
+    let session = session.into_iter().map(|path| (path, None));
+    let mut first = None;
+    for (i, (filename, position)) in session.chain(files).enumerate() {
+        match editor.open(Some(filename.clone()), position) {
+            Ok(index) if i >= first_given => first = first.or(Some(index)),
+            Ok(_) => {}
+            Err(e) => errors.push(format!("{}: {}", filename, e)),
         }
+    }
+    if editor.is_empty() {
+        editor.open(None, None).unwrap();
+    }
+    // start with the first file given, or the first of the session
+    editor.switch_to(first.unwrap_or(0));
+    if !errors.is_empty() {
+        editor.message(&errors.join(", "));
+    }
+    editor.run(&mut ctx);
+}
 
-        view.render(&text);
+// Files to edit along with the line and column to start at, given either as "file:line:col"
+// (the way compilers and grep -n print them) or as "+line file", and whether to restore the
+// previous session
+fn parse_args<I>(args: I) -> (Vec<FileArg>, bool)
+where
+    I: Iterator<Item = String>,
+{
+    let mut files = Vec::new();
+    let mut line = None;
+    let mut restore = false;
+    for arg in args {
+        if arg == "--restore" {
+            restore = true;
+            continue;
+        }
+        if let Some(Ok(n)) = arg.strip_prefix('+').map(str::parse::<usize>) {
+            line = Some(n);
+            continue;
+        }
+        let (filename, position) = split_position(&arg);
+        let position = position.or(line.map(|line| (line, 1)));
+        line = None;
+        let position = position.map(|(line, col)| (line.saturating_sub(1), col.saturating_sub(1)));
+        files.push((filename, position));
     }
+    (files, restore)
 }
 
-fn build_text(filename: &Option<String>) -> Select<Recorded<Text>> {
-    Select::new(Recorded::new(match filename {
-        Some(name) => match Text::open_file(name.clone()) {
-            Ok(v) => v,
-            Err(e) => panic!("{}", e),
+// Splits "file:line" and "file:line:col", unless a file is actually named that way
+fn split_position(arg: &str) -> (String, Option<(usize, usize)>) {
+    if Path::new(arg).exists() {
+        return (arg.to_string(), None);
+    }
+    let trimmed = arg.trim_end_matches(':');
+    let parts: Vec<&str> = trimmed.rsplitn(3, ':').collect();
+    match parts.as_slice() {
+        [col, line, file] if !file.is_empty() => {
+            if let (Ok(line), Ok(col)) = (line.parse(), col.parse()) {
+                return (file.to_string(), Some((line, col)));
+            }
+        }
+        _ => {}
+    }
+    let parts: Vec<&str> = trimmed.rsplitn(2, ':').collect();
+    match parts.as_slice() {
+        [line, file] if !file.is_empty() => match line.parse() {
+            Ok(line) => (file.to_string(), Some((line, 1))),
+            Err(_) => (arg.to_string(), None),
         },
-        None => Text::empty(),
-    }))
+        _ => (arg.to_string(), None),
+    }
 }
 
-fn build_view<'a>(filename: &Option<String>, ps: &'a SyntaxSet, theme: &'a Theme) -> View<'a> {
-    let syntax = match filename {
-        Some(filename) => match ps.find_syntax_for_file(filename) {
-            Ok(Some(syn)) => syn,
-            _ => ps.find_syntax_plain_text(),
-        },
-        None => ps.find_syntax_plain_text(),
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn args(args: &[&str]) -> (Vec<FileArg>, bool) {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn splits_line_and_col() {
+        assert_eq!(split_position("a.rs"), ("a.rs".to_string(), None));
+        assert_eq!(
+            split_position("a.rs:12"),
+            ("a.rs".to_string(), Some((12, 1)))
+        );
+        assert_eq!(
+            split_position("a.rs:12:5"),
+            ("a.rs".to_string(), Some((12, 5)))
+        );
+        assert_eq!(split_position("a.rs:0"), ("a.rs".to_string(), Some((0, 1))));
+    }
+
+    #[test]
+    fn ignores_trailing_colons() {
+        assert_eq!(
+            split_position("a.rs:12:"),
+            ("a.rs".to_string(), Some((12, 1)))
+        );
+        assert_eq!(
+            split_position("a.rs:12:5:"),
+            ("a.rs".to_string(), Some((12, 5)))
+        );
+        assert_eq!(
+            split_position("a.rs:12::"),
+            ("a.rs".to_string(), Some((12, 1)))
+        );
+    }
 
-    View::new(theme, syntax, ps)
+    #[test]
+    fn keeps_colons_in_paths() {
+        assert_eq!(
+            split_position("dir:x/a.rs:3:4"),
+            ("dir:x/a.rs".to_string(), Some((3, 4)))
+        );
+        assert_eq!(
+            split_position("dir:x/a.rs:3"),
+            ("dir:x/a.rs".to_string(), Some((3, 1)))
+        );
+        assert_eq!(split_position("a.rs:x"), ("a.rs:x".to_string(), None));
+        assert_eq!(split_position("a:b:c"), ("a:b:c".to_string(), None));
+        assert_eq!(split_position(":12"), (":12".to_string(), None));
+    }
+
+    #[test]
+    fn keeps_existing_file_named_with_position() {
+        let dir = env::temp_dir().join(format!("smith-args-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes:12");
+        fs::write(&file, "").unwrap();
+        let name = file.to_str().unwrap();
+        assert_eq!(split_position(name), (name.to_string(), None));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parses_positions_from_one() {
+        let (files, restore) = args(&["a.rs:12:5", "b.rs:0", "c.rs"]);
+        assert_eq!(
+            files,
+            vec![
+                ("a.rs".to_string(), Some((11, 4))),
+                ("b.rs".to_string(), Some((0, 0))),
+                ("c.rs".to_string(), None),
+            ]
+        );
+        assert!(!restore);
+    }
+
+    #[test]
+    fn plus_line_applies_to_next_file() {
+        let (files, _) = args(&["+12", "a.rs", "b.rs", "+0", "c.rs"]);
+        assert_eq!(
+            files,
+            vec![
+                ("a.rs".to_string(), Some((11, 0))),
+                ("b.rs".to_string(), None),
+                ("c.rs".to_string(), Some((0, 0))),
+            ]
+        );
+        // the position in the name wins
+        let (files, _) = args(&["+3", "a.rs:7"]);
+        assert_eq!(files, vec![("a.rs".to_string(), Some((6, 0)))]);
+        // not a number, so a file
+        let (files, _) = args(&["+x"]);
+        assert_eq!(files, vec![("+x".to_string(), None)]);
+    }
+
+    #[test]
+    fn restore_flag() {
+        let (files, restore) = args(&["a.rs", "--restore"]);
+        assert_eq!(files, vec![("a.rs".to_string(), None)]);
+        assert!(restore);
+    }
 }
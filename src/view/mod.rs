@@ -1,7 +1,10 @@
 mod screen;
 
-use self::screen::Screen;
-use crate::data::{Editable, Modifiable, Named, Selectable, Undoable};
+pub use self::screen::{CursorShape, Screen};
+use crate::config::{Settings, StatusLayout};
+use crate::data::{Editable, Modifiable, Named, Search, Selectable, Selection, Undoable};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use std::{cmp, iter};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::Theme;
@@ -11,34 +14,97 @@ use termion::terminal_size;
 pub struct View<'a> {
     message: Option<String>,
     is_prompt: bool,
+    // column of the cursor on the message line when prompting
+    prompt_cursor: usize,
     line_offset: usize,
-    screen: Screen,
+    screen: Rc<Screen>,
     theme: &'a Theme,
     syntax_set: &'a SyntaxSet,
     syntax_ref: &'a SyntaxReference,
+    settings: Settings,
+    last_click: Option<(Instant, u16, u16, usize)>,
+    search: Option<Search>,
+    // matches of the search in the whole text, found again only when the query changes, as the
+    // text can't be edited while searching
+    search_matches: Option<Vec<(usize, usize)>>,
+    // items of a list to pick from, along with the one picked
+    list: Option<(Vec<String>, usize)>,
+    // lines of the item picked in the list
+    preview: Option<Vec<String>>,
+    // part of the screen the view is drawn in, the message line aside
+    region: Rect,
+    // name of the editing mode shown in the status bar, and the shape of the cursor in it
+    mode: Option<&'static str>,
+    cursor_shape: CursorShape,
 }
 
-const TAB_LENGTH: usize = 4;
+/// A rectangle of cells on the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    // Screen area between the given number of rows at the top and the message line
+    pub fn main_area(top: usize) -> Rect {
+        // the size of a usual terminal when not in one, as when testing
+        let (width, height) = terminal_size().unwrap_or((80, 24));
+        Rect {
+            x: 0,
+            y: top,
+            width: width as usize,
+            height: (height as usize).saturating_sub(top + 1),
+        }
+    }
+
+    // Tells whether the cell at the given 1-based terminal coordinates lies inside
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        let (x, y) = (x as usize, y as usize);
+        x > self.x && x <= self.x + self.width && y > self.y && y <= self.y + self.height
+    }
+}
+
+const MULTI_CLICK_DELAY: Duration = Duration::from_millis(400);
+
+// Takes over the terminal, which all the views then share
+pub fn open_screen(theme: &Theme) -> Rc<Screen> {
+    let default_style = Style {
+        foreground: theme.settings.foreground.unwrap_or(Color::WHITE),
+        background: theme.settings.background.unwrap_or(Color::BLACK),
+        font_style: FontStyle::empty(),
+    };
+    Rc::new(Screen::with_default_style(default_style))
+}
 
 impl<'a> View<'a> {
     pub fn new(
         theme: &'a Theme,
         syntax_ref: &'a SyntaxReference,
         syntax_set: &'a SyntaxSet,
+        settings: Settings,
+        screen: Rc<Screen>,
     ) -> Self {
-        let default_style = Style {
-            foreground: theme.settings.foreground.unwrap_or(Color::WHITE),
-            background: theme.settings.background.unwrap_or(Color::BLACK),
-            font_style: FontStyle::empty(),
-        };
         View {
             message: None,
             is_prompt: false,
+            prompt_cursor: 0,
             line_offset: 0,
-            screen: Screen::with_default_style(default_style),
+            screen,
             theme,
             syntax_set,
             syntax_ref,
+            settings,
+            last_click: None,
+            search: None,
+            search_matches: None,
+            list: None,
+            preview: None,
+            region: Rect::main_area(0),
+            mode: None,
+            cursor_shape: CursorShape::Default,
         }
     }
 
@@ -48,7 +114,13 @@ impl<'a> View<'a> {
     }
 
     pub fn prompt(&mut self, prompt: &str, message: &str) {
+        self.prompt_at(prompt, message, message.chars().count());
+    }
+
+    // Prompts with the cursor on the given char of the message
+    pub fn prompt_at(&mut self, prompt: &str, message: &str, cursor: usize) {
         self.is_prompt = true;
+        self.prompt_cursor = prompt.chars().count() + cursor;
         let msg = String::from(prompt) + message;
         self.message = Some(msg);
     }
@@ -58,8 +130,83 @@ impl<'a> View<'a> {
         self.message = None;
     }
 
+    pub fn set_syntax(&mut self, syntax_ref: &'a SyntaxReference, settings: Settings) {
+        self.syntax_ref = syntax_ref;
+        self.settings = settings;
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    pub fn set_mode(&mut self, mode: &'static str, cursor_shape: CursorShape) {
+        self.mode = Some(mode);
+        self.cursor_shape = cursor_shape;
+    }
+
+    pub fn copy_to_terminal(&self, text: String) {
+        self.screen.set_clipboard(text);
+    }
+
+    // Highlights the matches of a search until it is reset
+    pub fn set_search(&mut self, search: Option<Search>) {
+        if search != self.search {
+            self.search = search;
+            self.search_matches = None;
+        }
+    }
+
+    pub fn region(&self) -> Rect {
+        self.region
+    }
+
+    pub fn set_region(&mut self, region: Rect) {
+        self.region = region;
+    }
+
+    pub fn show_list(&mut self, items: Vec<String>, selected: usize) {
+        self.list = Some((items, selected));
+    }
+
+    pub fn show_preview(&mut self, lines: Option<Vec<String>>) {
+        self.preview = lines;
+    }
+
+    pub fn hide_list(&mut self) {
+        self.list = None;
+        self.preview = None;
+    }
+
+    // Registers a click and returns how many quick successive clicks were made at that spot,
+    // cycling between single, double and triple clicks
+    pub fn click(&mut self, x: u16, y: u16) -> usize {
+        let now = Instant::now();
+        let count = match self.last_click {
+            Some((time, last_x, last_y, count))
+                if last_x == x && last_y == y && now.duration_since(time) < MULTI_CLICK_DELAY =>
+            {
+                count % 3 + 1
+            }
+            _ => 1,
+        };
+        self.last_click = Some((now, x, y, count));
+        count
+    }
+
+    pub fn line_offset(&self) -> usize {
+        self.line_offset
+    }
+
+    pub fn set_line_offset(&mut self, line_offset: usize) {
+        self.line_offset = line_offset;
+    }
+
     pub fn center_view(&mut self, line: usize) {
-        self.line_offset = line.saturating_sub(self.lines_height() as usize / 2);
+        self.line_offset = line.saturating_sub(self.lines_height() / 2);
     }
 
     pub fn adjust_view(&mut self, line: usize) {
@@ -77,46 +224,205 @@ impl<'a> View<'a> {
         ) as usize;
     }
 
-    pub fn render<T>(&mut self, content: &T)
+    // Paints the view in its region, the focused one also getting the cursor and the message line
+    pub fn render<T>(&mut self, content: &T, focused: bool)
     where
         T: Editable + Named + Selectable + Undoable + Modifiable,
     {
-        self.screen.clear();
+        if let (Some(ref search), None) = (&self.search, &self.search_matches) {
+            self.search_matches = Some(search.find_all(content));
+        }
         self.paint_lines(content);
-        self.paint_status(content);
-        self.paint_message();
-        self.paint_cursor(content);
-        self.screen.present();
+        self.paint_status(content, focused);
+        if focused {
+            self.paint_list();
+            self.paint_message();
+            self.paint_cursor(content);
+        }
     }
 
     pub fn translate_coordinates<T>(&self, content: &T, x: u16, y: u16) -> (usize, usize)
+    where
+        T: Editable,
+    {
+        let (line, visual_col) = self.translate_visual_coordinates(content, x, y);
+        (line, self.col_at(content, line, visual_col))
+    }
+
+    // Translates screen coordinates to a line and a visual column, which may lie past the end of
+    // the line
+    pub fn translate_visual_coordinates<T>(&self, content: &T, x: u16, y: u16) -> (usize, usize)
     where
         T: Editable,
     {
         let line = cmp::min(
-            (y as isize + self.line_offset as isize - 1) as usize,
+            cmp::max(self.row_of(y), 0) as usize + self.line_offset,
             content.line_count() - 1,
         );
         let visual_col = (cmp::max(
             0,
-            x as isize - self.line_number_width(content.line_count()) as isize - 2,
+            x as isize
+                - self.region.x as isize
+                - self.line_number_width(content.line_count()) as isize
+                - 2,
         )) as usize;
-        // find out if we clicked through a tab
-        let col = content
+        (line, visual_col)
+    }
+
+    // Row of the text area at the given 1-based terminal row, negative above the view
+    pub fn row_of(&self, y: u16) -> isize {
+        y as isize - 1 - self.region.y as isize
+    }
+
+    // Column of the char displayed at visual_col, or of the end of the line if it is too short
+    pub fn col_at<T>(&self, content: &T, line: usize, visual_col: usize) -> usize
+    where
+        T: Editable,
+    {
+        // find out if we went through a tab
+        content
             .iter_line(line)
+            .take_while(|&c| c != '\n')
             .scan(0, |state, x| {
-                *state += if x == '\t' { TAB_LENGTH } else { 1 };
+                *state += self.char_width(x, *state);
                 Some(*state)
             })
             .take_while(|&x| x <= visual_col)
-            .count();
-        (line, col)
+            .count()
+    }
+
+    // Visual column at which the char at col is displayed
+    pub fn visual_col<T>(&self, content: &T, line: usize, col: usize) -> usize
+    where
+        T: Editable,
+    {
+        // we can't trust the actual column because tabs have variable length
+        content
+            .iter_line(line)
+            .take(col)
+            .fold(0, |visual, x| visual + self.char_width(x, visual))
+    }
+
+    // Number of columns taken by c when displayed at visual_col, tabs going up to the next stop
+    fn char_width(&self, c: char, visual_col: usize) -> usize {
+        if c == '\t' {
+            let width = self.settings.tab_width;
+            width - visual_col % width
+        } else {
+            1
+        }
+    }
+
+    // Line as displayed, tabs expanded to the next stop
+    fn expand_tabs(&self, line: &str) -> String {
+        let mut expanded = String::new();
+        for c in line.chars() {
+            if c == '\t' {
+                let width = self.char_width(c, expanded.chars().count());
+                expanded.extend(iter::repeat_n(' ', width));
+            } else {
+                expanded.push(c);
+            }
+        }
+        expanded
     }
 
     fn paint_message(&self) {
         if let Some(ref message) = self.message {
-            let y = self.lines_height() + 1;
-            self.screen.draw(0, y, message);
+            self.screen.draw(0, self.message_row(), message);
+        }
+    }
+
+    fn message_row(&self) -> usize {
+        let (_, screen_height) = terminal_size().unwrap();
+        (screen_height as usize).saturating_sub(1)
+    }
+
+    // Draws inside the region, cutting what goes past its right edge
+    fn draw_ranges(&self, x: usize, y: usize, ranges: Vec<(Style, &str)>) {
+        if y >= self.region.height {
+            return;
+        }
+        self.screen.draw_ranges_within(
+            self.region.x + x,
+            self.region.y + y,
+            self.region.x + self.region.width,
+            ranges,
+        );
+    }
+
+    fn draw_with_style(&self, x: usize, y: usize, style: Style, text: &str) {
+        self.draw_ranges(x, y, vec![(style, text)]);
+    }
+
+    // Draws the list over the bottom of the text, scrolled to show the picked item
+    fn paint_list(&self) {
+        let (items, selected) = match self.list {
+            Some((ref items, selected)) => (items, selected),
+            None => return,
+        };
+        // no room for it in a pane too small to show text
+        if self.lines_height() == 0 {
+            return;
+        }
+        let height = cmp::min(items.len(), cmp::max(self.lines_height() / 2, 1));
+        let first = (selected + 1).saturating_sub(height);
+        let top = self.lines_height().saturating_sub(height);
+
+        let style = Style {
+            foreground: self.theme.settings.foreground.unwrap_or(Color::WHITE),
+            background: self.theme.settings.gutter.unwrap_or(Color {
+                r: 40,
+                g: 40,
+                b: 40,
+                a: 255,
+            }),
+            font_style: FontStyle::empty(),
+        };
+        let selected_style = Style {
+            foreground: self
+                .theme
+                .settings
+                .selection_foreground
+                .unwrap_or(Color::WHITE),
+            background: self.theme.settings.selection.unwrap_or(Color::BLACK),
+            font_style: FontStyle::empty(),
+        };
+        for (i, item) in items.iter().enumerate().skip(first).take(height) {
+            let style = if i == selected { selected_style } else { style };
+            let line: String = format!(" {}", item)
+                .chars()
+                .chain(iter::repeat(' '))
+                .take(self.region.width)
+                .collect();
+            self.draw_with_style(0, top + i - first, style, &line);
+        }
+        if let Some(ref lines) = self.preview {
+            self.paint_preview(lines, top);
+        }
+    }
+
+    // Draws the preview over the text left above the list
+    fn paint_preview(&self, lines: &[String], height: usize) {
+        let style = Style {
+            foreground: self.theme.settings.foreground.unwrap_or(Color::WHITE),
+            background: self.theme.settings.background.unwrap_or(Color::BLACK),
+            font_style: FontStyle::empty(),
+        };
+        let empty = String::new();
+        for (y, text) in lines
+            .iter()
+            .chain(iter::repeat(&empty))
+            .take(height)
+            .enumerate()
+        {
+            let line: String = self
+                .expand_tabs(text)
+                .chars()
+                .chain(iter::repeat(' '))
+                .take(self.region.width)
+                .collect();
+            self.draw_with_style(0, y, style, &line);
         }
     }
 
@@ -124,87 +430,160 @@ impl<'a> View<'a> {
     where
         T: Editable + Selectable,
     {
+        self.screen.set_cursor_shape(self.cursor_shape);
+        // in the case of a prompt, the cursor should be drawn in the message line
+        if self.is_prompt {
+            self.screen
+                .move_cursor(self.prompt_cursor, self.message_row());
+            self.screen.show_cursor();
+            return;
+        }
+
         // FIXME: don't print the cursor if off screen, though we should in the future for long
         // lines
         if (content.line()) < self.line_offset
             || content.line() >= self.line_offset + self.lines_height()
             || content.col() >= self.lines_width(content.line_count())
-            || content.sel().is_some()
         {
             self.screen.hide_cursor();
             return;
         }
 
-        // in the case of a prompt, the cursor should be drawn in the message line
-        let (x, y) = if self.is_prompt {
-            (
-                self.message.clone().unwrap().chars().count(),
-                self.lines_height() + 1,
-            )
-        } else {
-            let (a, b) = self.cursor_pos(content);
-            (a, b)
-        };
+        let (a, b) = self.cursor_pos(content);
+        let (x, y) = (self.region.x + a, self.region.y + b);
         self.screen.move_cursor(x, y);
         self.screen.show_cursor();
     }
 
-    fn paint_status<T>(&self, content: &T)
+    fn paint_status<T>(&self, content: &T, focused: bool)
     where
-        T: Editable + Named + Undoable + Modifiable,
+        T: Editable + Named + Undoable + Modifiable + Selectable,
     {
         let line = content.line();
         let column = content.col();
         let line_count = content.line_count();
         let advance = ((line + 1) as f64 / line_count as f64 * 100.0).floor();
 
-        let (screen_width, _) = terminal_size().unwrap();
-        let empty_line = (0..screen_width).map(|_| ' ').collect::<String>();
+        let width = self.region.width;
+        let empty_line = (0..width).map(|_| ' ').collect::<String>();
         let y = self.lines_height();
 
+        // the status of the other views is dimmed
+        let foreground = if focused {
+            self.theme.settings.foreground
+        } else {
+            self.theme.settings.gutter_foreground
+        };
         let style = Style {
             background: self.theme.settings.background.unwrap_or(Color::BLACK),
-            foreground: self.theme.settings.foreground.unwrap_or(Color::WHITE),
+            foreground: foreground.unwrap_or(Color::WHITE),
             font_style: FontStyle::empty(),
         };
 
-        self.screen.draw_with_style(0, y, style, &empty_line);
-        let mut filename = content.name().clone();
-        if content.was_modified() {
-            filename.push_str(" *");
-        }
-        self.screen.draw_with_style(0, y, style, &filename);
-
-        let position_info = format!("{}% {}/{}: {}", advance, line + 1, line_count, column);
-        let x = screen_width as usize - position_info.len();
-        self.screen.draw_with_style(x, y, style, &position_info);
+        self.draw_with_style(0, y, style, &empty_line);
+        let matches = match self.search_matches {
+            Some(ref matches) => {
+                let current = content
+                    .sel()
+                    .and_then(|sel| matches.iter().position(|m| m.0 == sel.beg()));
+                match current {
+                    _ if matches.is_empty() => "No matches".to_string(),
+                    Some(i) => format!("{}/{} matches", i + 1, matches.len()),
+                    None => format!("{} matches", matches.len()),
+                }
+            }
+            None => String::new(),
+        };
+        let mode = match (self.mode, focused) {
+            (Some(mode), true) => mode.to_string(),
+            _ => String::new(),
+        };
+        let modified = if content.was_modified() { "*" } else { "" };
+        let fields = [
+            ("mode", mode),
+            ("name", content.name().clone()),
+            ("modified", modified.to_string()),
+            ("syntax", self.syntax_ref.name.clone()),
+            ("matches", matches),
+            ("percent", advance.to_string()),
+            ("line", (line + 1).to_string()),
+            ("lines", line_count.to_string()),
+            ("column", column.to_string()),
+        ];
+        let status = &self.settings.status;
+        self.draw_with_style(0, y, style, &StatusLayout::fill(&status.left, &fields));
+
+        let position_info = StatusLayout::fill(&status.right, &fields);
+        let x = width.saturating_sub(position_info.chars().count());
+        self.draw_with_style(x, y, style, &position_info);
     }
 
     fn paint_lines<T>(&mut self, content: &T)
     where
         T: Editable + Selectable,
     {
-        let line_offset = self.line_offset as usize;
-        let lines_height = self.lines_height() as usize;
+        let line_offset = self.line_offset;
+        let lines_height = self.lines_height();
         let line_count = content.line_count();
 
         let line_start = self.line_number_width(line_count) as usize + 1;
 
         let mut highlighter = HighlightLines::new(self.syntax_ref, self.theme);
 
+        let selection_style = Style {
+            foreground: self
+                .theme
+                .settings
+                .selection_foreground
+                .unwrap_or(Color::WHITE),
+            background: self.theme.settings.selection.unwrap_or(Color::BLACK),
+            font_style: FontStyle::empty(),
+        };
+        let cursor_style = Style {
+            foreground: self.theme.settings.background.unwrap_or(Color::BLACK),
+            background: self.theme.settings.caret.unwrap_or(Color::WHITE),
+            font_style: FontStyle::empty(),
+        };
+        let match_style = Style {
+            foreground: self
+                .theme
+                .settings
+                .find_highlight_foreground
+                .unwrap_or(Color::BLACK),
+            background: self.theme.settings.find_highlight.unwrap_or(Color {
+                r: 250,
+                g: 189,
+                b: 47,
+                a: 255,
+            }),
+            font_style: FontStyle::empty(),
+        };
+        // other occurrences of what is under the cursor, unless a search is going on
+        let occurrences = match self.search {
+            Some(_) => None,
+            None => Search::under_cursor(content).map(|(search, _)| search),
+        };
+        let occurrence_background = self
+            .theme
+            .settings
+            .line_highlight
+            .or(self.theme.settings.highlight)
+            .unwrap_or(Color {
+                r: 60,
+                g: 56,
+                b: 54,
+                a: 255,
+            });
+        let selections: Vec<Selection> = content
+            .cursors()
+            .iter()
+            .chain(content.sel().iter())
+            .filter(|sel| !sel.is_empty())
+            .cloned()
+            .collect();
+
         for (i, line) in content.lines().enumerate() {
-            let line_str = line
-                .chars()
-                .flat_map(|c| {
-                    if c == '\t' {
-                        iter::repeat(' ').take(TAB_LENGTH) // FIXME: selection should consider tabs
-                    } else if c == '\n' {
-                        iter::repeat(' ').take(1)
-                    } else {
-                        iter::repeat(c).take(1)
-                    }
-                })
-                .collect::<String>();
+            let line_str = self.expand_tabs(&line.to_string()).replace('\n', " ");
 
             let ranges: Vec<(Style, &str)> = highlighter.highlight(&line_str, self.syntax_set);
 
@@ -233,59 +612,40 @@ impl<'a> View<'a> {
                 }),
                 font_style: FontStyle::empty(),
             };
-            self.screen
-                .draw_with_style(0, y, line_number_style, &format!("{}", 1 + line_index));
-
-            self.screen.draw_ranges(line_start, y, ranges);
-
-            // draw selection over
-            if let Some((selbeg, selend)) = content.sel() {
-                let selection_style = Style {
-                    foreground: self
-                        .theme
-                        .settings
-                        .selection_foreground
-                        .unwrap_or(Color::WHITE),
-                    background: self.theme.settings.selection.unwrap_or(Color::BLACK),
-                    font_style: FontStyle::empty(),
-                };
-                let beg = content.line_index_to_char_index(line_index);
-                let end = beg + line_str.len() - 1;
-
-                if *selbeg <= beg && *selend >= end {
-                    // line is fully inside the selection
-                    if line_str.is_empty() {
-                        self.screen
-                            .draw_with_style(line_start, y, selection_style, " ");
-                    } else {
-                        self.screen
-                            .draw_with_style(line_start, y, selection_style, &line_str);
-                    }
-                } else if *selbeg >= beg && *selbeg <= end && *selend <= end && *selend >= beg {
-                    // selection is inside the line
-                    self.screen.draw_with_style(
-                        line_start + (*selbeg - beg),
-                        y,
-                        selection_style,
-                        &line_str[(*selbeg - beg)..=(*selend - beg)],
-                    );
-                } else if *selbeg <= end && *selbeg >= beg {
-                    // line contains the beginning of the selection
-                    self.screen.draw_with_style(
-                        line_start + (*selbeg - beg),
-                        y,
-                        selection_style,
-                        &line_str[(*selbeg - beg)..],
-                    );
-                } else if *selend <= end && *selend >= beg {
-                    // line contains the end of the selection
-                    self.screen.draw_with_style(
-                        line_start,
-                        y,
-                        selection_style,
-                        &line_str[..=(*selend - beg)],
-                    );
+            if self.settings.line_numbers {
+                self.draw_with_style(0, y, line_number_style, &format!("{}", 1 + line_index));
+            }
+
+            self.draw_ranges(line_start, y, ranges);
+
+            // draw search matches, selections and secondary cursors over
+            let beg = content.line_index_to_char_index(line_index);
+            let chars: Vec<char> = line.chars().collect();
+            let matches = match self.search {
+                Some(ref search) => search.find_in(&chars),
+                None => Vec::new(),
+            };
+            if let Some(ref search) = occurrences {
+                for (b, e) in search.find_in(&chars) {
+                    let x = line_start + self.visual_col(content, line_index, b);
+                    let len = self.visual_col(content, line_index, e) + line_start - x;
+                    self.highlight(x, y, len, occurrence_background);
+                }
+            }
+            let mut x = line_start;
+            for (i, c) in line.chars().enumerate() {
+                let width = self.char_width(c, x - line_start);
+                let shown = if c == '\t' || c == '\n' { ' ' } else { c };
+                if content.cursors().iter().any(|other| other.head == beg + i) {
+                    self.draw_with_style(x, y, cursor_style, &shown.to_string());
+                } else if selections.iter().any(|sel| sel.contains(beg + i)) {
+                    let text: String = iter::repeat_n(shown, width).collect();
+                    self.draw_with_style(x, y, selection_style, &text);
+                } else if matches.iter().any(|&(b, e)| i >= b && i < e) {
+                    let text: String = iter::repeat_n(shown, width).collect();
+                    self.draw_with_style(x, y, match_style, &text);
                 }
+                x += width;
             }
         }
     }
@@ -294,14 +654,8 @@ impl<'a> View<'a> {
         // TODO: column offsetting for long lines
         let line = content.line();
         let first_line = self.line_offset;
-        let y = line - first_line as usize;
-        // we can't trust the actual column because tabs have variable length
-        let visual_col = content.col();
-        let column: usize = content
-            .iter_line(line)
-            .map(|x| if x == '\t' { TAB_LENGTH } else { 1 })
-            .take(visual_col)
-            .sum();
+        let y = line - first_line;
+        let column = self.visual_col(content, line, content.col());
         (
             (self.line_number_width(content.line_count()) as usize + 1 + column),
             y,
@@ -309,22 +663,33 @@ impl<'a> View<'a> {
     }
 
     fn line_number_width(&self, line_count: usize) -> u16 {
+        if !self.settings.line_numbers {
+            return 0;
+        }
         line_count.to_string().len() as u16
     }
 
     fn status_height(&self) -> u16 {
-        2
+        1
     }
 
     pub fn lines_height(&self) -> usize {
-        let (_, screen_height) = terminal_size().unwrap();
         let incompressible = self.status_height() as usize;
-        cmp::max(screen_height as usize, incompressible) - incompressible
+        cmp::max(self.region.height, incompressible) - incompressible
     }
 
     pub fn lines_width(&self, line_count: usize) -> usize {
-        let (screen_width, _) = terminal_size().unwrap();
         let incompressible = self.line_number_width(line_count) as usize + 1;
-        cmp::max(screen_width as usize, incompressible) - incompressible
+        cmp::max(self.region.width, incompressible) - incompressible
+    }
+
+    // Changes the background of cells already drawn in the region
+    fn highlight(&self, x: usize, y: usize, len: usize, background: Color) {
+        if y >= self.region.height || x >= self.region.width {
+            return;
+        }
+        let len = cmp::min(len, self.region.width - x);
+        self.screen
+            .highlight(self.region.x + x, self.region.y + y, len, background);
     }
 }
@@ -1,47 +1,78 @@
 use ndarray::{Array, Array2};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::cmp;
 use std::fmt::Write as FmtWrite;
 use std::io;
 use std::io::{BufWriter, Write};
-use syntect::highlighting::Style;
+use syntect::highlighting::{Color, Style};
 use termion::color;
 use termion::input::MouseTerminal;
-use termion::raw::{IntoRawMode, RawTerminal};
+use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 
+const ENABLE_BRACKETED_PASTE: &str = "\x1b[?2004h";
+const DISABLE_BRACKETED_PASTE: &str = "\x1b[?2004l";
+const RESET_CURSOR_SHAPE: &str = "\x1b[0 q";
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Shape of the terminal cursor, which tells the modes apart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorShape {
+    // whatever the terminal uses by default
+    Default,
+    Block,
+    Bar,
+}
+
 pub struct Screen {
-    out: RefCell<RawTerminal<MouseTerminal<AlternateScreen<BufWriter<io::Stdout>>>>>,
+    out: RefCell<Box<dyn Write>>,
     write_buf: RefCell<Array2<(Style, char)>>,
     read_buf: RefCell<Array2<(Style, char)>>,
-    cursor_pos: (usize, usize),
-    cursor_visible: bool,
+    cursor_pos: Cell<(usize, usize)>,
+    cursor_visible: Cell<bool>,
+    cursor_shape: Cell<CursorShape>,
+    // shape the terminal was last told to use
+    written_shape: Cell<CursorShape>,
+    // text to put in the terminal's clipboard along with the next frame
+    clipboard: RefCell<Option<String>>,
     default_style: Style,
 }
 
 impl Screen {
     pub fn with_default_style(default_style: Style) -> Self {
         let (w, h) = termion::terminal_size().unwrap();
-        let write_buf: Array<_, _> = std::iter::repeat((default_style, ' '))
-            .take(w as usize * h as usize)
-            .collect();
-        let write_buf = write_buf.into_shape((h as usize, w as usize)).unwrap();
-        let read_buf: Array<_, _> =
-            (std::iter::repeat((default_style, 'X')).take(w as usize * h as usize)).collect();
-        let read_buf = read_buf.into_shape((h as usize, w as usize)).unwrap();
-        let out = RefCell::new(
-            MouseTerminal::from(AlternateScreen::from(BufWriter::with_capacity(
-                1 << 14,
-                io::stdout(),
-            )))
-            .into_raw_mode()
-            .unwrap(),
-        );
+        let out = MouseTerminal::from(AlternateScreen::from(BufWriter::with_capacity(
+            1 << 14,
+            io::stdout(),
+        )))
+        .into_raw_mode()
+        .unwrap();
+        let screen = Screen::with_output(Box::new(out), w as usize, h as usize, default_style);
+        // have the terminal tell pasted text apart from typed text
+        write!(screen.out.borrow_mut(), "{}", ENABLE_BRACKETED_PASTE).unwrap();
+        screen
+    }
+
+    // Screen drawing nowhere, for testing what is drawn on it
+    #[cfg(test)]
+    pub fn headless(width: usize, height: usize) -> Self {
+        Screen::with_output(Box::new(io::sink()), width, height, Style::default())
+    }
+
+    fn with_output(out: Box<dyn Write>, w: usize, h: usize, default_style: Style) -> Self {
+        let write_buf: Array<_, _> = std::iter::repeat_n((default_style, ' '), w * h).collect();
+        let write_buf = write_buf.into_shape((h, w)).unwrap();
+        let read_buf: Array<_, _> = std::iter::repeat_n((default_style, 'X'), w * h).collect();
+        let read_buf = read_buf.into_shape((h, w)).unwrap();
         Screen {
-            out,
+            out: RefCell::new(out),
             read_buf: RefCell::new(read_buf),
             write_buf: RefCell::new(write_buf),
-            cursor_pos: (0, 0),
-            cursor_visible: true,
+            cursor_pos: Cell::new((0, 0)),
+            cursor_visible: Cell::new(true),
+            cursor_shape: Cell::new(CursorShape::Default),
+            written_shape: Cell::new(CursorShape::Default),
+            clipboard: RefCell::new(None),
             default_style,
         }
     }
@@ -83,8 +114,23 @@ impl Screen {
             }
         }
 
-        if self.cursor_visible {
-            let (cx, cy) = self.cursor_pos;
+        if self.cursor_shape.get() != self.written_shape.get() {
+            let shape = match self.cursor_shape.get() {
+                CursorShape::Default => RESET_CURSOR_SHAPE,
+                CursorShape::Block => "\x1b[2 q",
+                CursorShape::Bar => "\x1b[6 q",
+            };
+            write!(out, "{}", shape).unwrap();
+            self.written_shape.set(self.cursor_shape.get());
+        }
+
+        if let Some(text) = self.clipboard.borrow_mut().take() {
+            // OSC 52, which also works over SSH
+            write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes())).unwrap();
+        }
+
+        if self.cursor_visible.get() {
+            let (cx, cy) = self.cursor_pos.get();
             write!(
                 out,
                 "{}{}",
@@ -106,11 +152,17 @@ impl Screen {
     }
 
     pub fn draw_ranges(&self, x: usize, y: usize, ranges: Vec<(Style, &str)>) {
+        self.draw_ranges_within(x, y, usize::MAX, ranges);
+    }
+
+    // Draws the ranges, leaving the cells from max_x onward untouched
+    pub fn draw_ranges_within(&self, x: usize, y: usize, max_x: usize, ranges: Vec<(Style, &str)>) {
         let mut write_buf = self.write_buf.borrow_mut();
         let (h, w) = write_buf.dim();
         if y >= h {
             return;
         }
+        let w = cmp::min(w, max_x);
         let mut x = x;
         for (style, text) in ranges {
             for g in text.chars() {
@@ -123,16 +175,37 @@ impl Screen {
         }
     }
 
-    pub fn hide_cursor(&mut self) {
-        self.cursor_visible = false;
+    // Changes the background of what was already drawn, keeping its colors
+    pub fn highlight(&self, x: usize, y: usize, len: usize, background: Color) {
+        let mut write_buf = self.write_buf.borrow_mut();
+        let (h, w) = write_buf.dim();
+        if y >= h {
+            return;
+        }
+        for x in x..cmp::min(x + len, w) {
+            write_buf[[y, x]].0.background = background;
+        }
+    }
+
+    pub fn hide_cursor(&self) {
+        self.cursor_visible.set(false);
+    }
+
+    pub fn show_cursor(&self) {
+        self.cursor_visible.set(true);
     }
 
-    pub fn show_cursor(&mut self) {
-        self.cursor_visible = true;
+    pub fn move_cursor(&self, x: usize, y: usize) {
+        self.cursor_pos.set((x, y));
     }
 
-    pub fn move_cursor(&mut self, x: usize, y: usize) {
-        self.cursor_pos = (x, y);
+    pub fn set_cursor_shape(&self, shape: CursorShape) {
+        self.cursor_shape.set(shape);
+    }
+
+    // Asks the terminal to set its clipboard, once the next frame is presented
+    pub fn set_clipboard(&self, text: String) {
+        *self.clipboard.borrow_mut() = Some(text);
     }
 
     fn escape_style(style: &Style) -> String {
@@ -153,16 +226,72 @@ impl Screen {
     }
 }
 
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let indices = [
+            b[0] >> 2,
+            (b[0] & 0b11) << 4 | b[1] >> 4,
+            (b[1] & 0b1111) << 2 | b[2] >> 6,
+            b[2] & 0b11_1111,
+        ];
+        for (i, &index) in indices.iter().enumerate() {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
 impl Drop for Screen {
     fn drop(&mut self) {
+        let shape = if self.written_shape.get() == CursorShape::Default {
+            ""
+        } else {
+            RESET_CURSOR_SHAPE
+        };
         write!(
             self.out.borrow_mut(),
-            "{}{}{}",
+            "{}{}{}{}{}",
             color::Fg(color::Reset),
             color::Bg(color::Reset),
             termion::clear::All,
+            DISABLE_BRACKETED_PASTE,
+            shape,
         )
         .unwrap();
         self.show_cursor();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_test_vectors() {
+        // from RFC 4648
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn base64_high_bits() {
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+        assert_eq!(base64(&[0xff]), "/w==");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+}